@@ -0,0 +1,189 @@
+use crate::wav::WavWriter;
+use std::error::Error;
+use std::path::Path;
+
+pub const SAMPLE_RATE: u32 = 44100;
+const CPU_CLOCK: u32 = 1_000_000;
+const BUFFER_SAMPLES: usize = 512;
+const AMPLITUDE: i32 = 6000;
+const DC_BLOCK: f32 = 0.995;
+
+pub trait AudioSink {
+    fn push_samples(&mut self, samples: &[i16]);
+}
+
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn push_samples(&mut self, _samples: &[i16]) {}
+}
+
+pub struct Sound {
+    sink: Box<dyn AudioSink>,
+    phase: u32,
+    high_cycles: i32,
+    total_cycles: i32,
+    last_input: f32,
+    last_output: f32,
+    buffer: Vec<i16>,
+    recorder: Option<WavWriter>,
+    record_error: Option<Box<dyn Error>>,
+}
+
+impl Sound {
+    pub fn new() -> Self {
+        Self {
+            sink: Box::new(NullSink),
+            phase: 0,
+            high_cycles: 0,
+            total_cycles: 0,
+            last_input: 0.0,
+            last_output: 0.0,
+            buffer: Vec::with_capacity(BUFFER_SAMPLES),
+            recorder: None,
+            record_error: None,
+        }
+    }
+
+    pub fn set_sink(&mut self, sink: Box<dyn AudioSink>) {
+        self.sink = sink;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some() || self.record_error.is_some()
+    }
+
+    pub fn start_recording(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.stop_recording()?;
+        self.sink.push_samples(&self.buffer);
+        self.buffer.clear();
+        self.recorder = Some(WavWriter::create(path, SAMPLE_RATE)?);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(error) = self.record_error.take() {
+            return Err(error);
+        }
+        match self.recorder.take() {
+            Some(mut recorder) => {
+                recorder.write_samples(&self.buffer)?;
+                recorder.finish()
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn tick(&mut self, level: bool) {
+        self.total_cycles += 1;
+        if level {
+            self.high_cycles += 1;
+        }
+        self.phase += SAMPLE_RATE;
+        if self.phase < CPU_CLOCK {
+            return;
+        }
+        self.phase -= CPU_CLOCK;
+
+        let input =
+            (AMPLITUDE * (2 * self.high_cycles - self.total_cycles) / self.total_cycles) as f32;
+        self.last_output = input - self.last_input + DC_BLOCK * self.last_output;
+        self.last_input = input;
+        self.buffer.push(self.last_output as i16);
+        self.high_cycles = 0;
+        self.total_cycles = 0;
+        if self.buffer.len() == BUFFER_SAMPLES {
+            self.sink.push_samples(&self.buffer);
+            let written = self
+                .recorder
+                .as_mut()
+                .map(|recorder| recorder.write_samples(&self.buffer));
+            if let Some(Err(e)) = written {
+                self.record_error = Some(e);
+                self.recorder = None;
+            }
+            self.buffer.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Capture(Rc<RefCell<Vec<i16>>>);
+
+    impl AudioSink for Capture {
+        fn push_samples(&mut self, samples: &[i16]) {
+            self.0.borrow_mut().extend_from_slice(samples);
+        }
+    }
+
+    #[test]
+    fn test_wav_recording() {
+        let path = std::env::temp_dir().join("pet4032_test_recording.wav");
+        let mut sound = Sound::new();
+        sound.start_recording(&path).unwrap();
+        for cycle in 0..100_000 {
+            sound.tick((cycle / 500) % 2 == 0);
+        }
+        sound.stop_recording().unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(&data[8..16], b"WAVEfmt ");
+        assert_eq!(
+            u32::from_le_bytes([data[40], data[41], data[42], data[43]]),
+            4410 * 2
+        );
+        assert_eq!(data.len(), 44 + 4410 * 2);
+        assert!(!sound.is_recording());
+    }
+
+    #[test]
+    fn test_square_wave_samples() {
+        let captured = Rc::new(RefCell::new(Vec::new()));
+        let mut sound = Sound::new();
+        sound.set_sink(Box::new(Capture(captured.clone())));
+        for cycle in 0..100_000 {
+            sound.tick((cycle / 500) % 2 == 0);
+        }
+        let samples = captured.borrow();
+        assert_eq!(samples.len(), 4096);
+        assert_eq!(samples[0], AMPLITUDE as i16);
+        assert!(samples[30] < -AMPLITUDE as i16 / 2);
+        let mean = samples.iter().map(|&s| s as i64).sum::<i64>() / samples.len() as i64;
+        assert!(mean.abs() < 100);
+    }
+
+    #[test]
+    fn test_static_level_is_silent() {
+        let captured = Rc::new(RefCell::new(Vec::new()));
+        let mut sound = Sound::new();
+        sound.set_sink(Box::new(Capture(captured.clone())));
+        for _ in 0..100_000 {
+            sound.tick(true);
+        }
+        let samples = captured.borrow();
+        assert_eq!(samples[0], AMPLITUDE as i16);
+        assert!(samples[samples.len() - 1].abs() < 10);
+    }
+
+    #[test]
+    fn test_recording_starts_empty() {
+        let path = std::env::temp_dir().join("pet4032_test_restart.wav");
+        let mut sound = Sound::new();
+        for _ in 0..5_000 {
+            sound.tick(false);
+        }
+        sound.start_recording(&path).unwrap();
+        sound.stop_recording().unwrap();
+        sound.start_recording(&path).unwrap();
+        sound.stop_recording().unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(data.len(), 44);
+    }
+}
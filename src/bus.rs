@@ -1,30 +1,55 @@
+use crate::audio::Sound;
 use crate::crtc6845::Crtc6845;
+use crate::datasette::Datasette;
+use crate::ieee488::{Ieee488Bus, Ieee488Lines};
+use crate::machine::Model;
+use crate::memory_expansion::MemoryExpansion;
 use crate::pia6821::Pia6821;
-use crate::rom_loader::RomData;
-use crate::via6522::Via6522;
+use crate::rom_loader::{OptionSocket, RomData};
+use crate::via6522::{Via6522, ViaControlLines};
 use mos6502::bus::Bus as CpuBus;
 
 pub struct PetBus {
+    pub model: Model,
     pub ram: [u8; 0x8800],
+    pub ram_size: usize,
+    pub expansion: Option<MemoryExpansion>,
     pub roms: RomData,
+    pub option_sockets: [Option<OptionSocket>; 2],
     pub via: Via6522,
-    pub pia: Pia6821,
+    pub pia1: Pia6821,
+    pub pia2: Pia6821,
     pub crtc: Crtc6845,
+    pub ieee: Ieee488Bus,
+    pub datasettes: [Datasette; 2],
+    pub sound: Sound,
     pub irq_asserted: bool,
+    pub kernal_traps: bool,
     pub total_cycles: u64,
 }
 
 impl PetBus {
-    pub fn new(roms: RomData) -> Self {
+    pub fn new(roms: RomData, model: Model, ram_size: usize) -> Self {
         let mut crtc = Crtc6845::new();
-        crtc.init_pet4032_screen();
+        crtc.init_screen(model);
         Self {
+            model,
             ram: [0; 0x8800],
+            ram_size,
+            expansion: model
+                .memory_expansion()
+                .then(|| MemoryExpansion::new([model.socket_ram(); 2])),
             roms,
+            option_sockets: [None, None],
             via: Via6522::new(),
-            pia: Pia6821::new(),
+            pia1: Pia6821::new(),
+            pia2: Pia6821::new(),
             crtc,
+            ieee: Ieee488Bus::new(),
+            datasettes: [Datasette::new(), Datasette::new()],
+            sound: Sound::new(),
             irq_asserted: false,
+            kernal_traps: model.basic_version() == 4,
             total_cycles: 0,
         }
     }
@@ -32,32 +57,140 @@ impl PetBus {
     pub fn tick(&mut self) {
         self.total_cycles += 1;
         self.via.tick(1);
-        self.pia.tick(1);
+        self.pia1.tick(1);
+        self.pia2.tick(1);
+        self.crtc.tick();
+        self.pia1.set_cb1(!self.crtc.vsync());
+        let lines = self.update_ieee();
+        self.update_datasette();
+        self.sound.tick(self.via.control_lines().cb2);
+
+        let mut pia1_in = 0xFF;
+        if self.datasettes[0].sense() {
+            pia1_in &= !0x10;
+        }
+        if self.datasettes[1].sense() {
+            pia1_in &= !0x20;
+        }
+        if lines.eoi {
+            pia1_in &= !0x40;
+        }
+        self.pia1.set_port_a_input(pia1_in);
 
         let via_irq = self.via.irq_out;
-        let pia_irq = self.pia.irq_out();
+        let pia_irq = self.pia1.irq_out() || self.pia2.irq_out();
         self.irq_asserted = via_irq || pia_irq;
     }
+
+    fn update_ieee(&mut self) -> Ieee488Lines {
+        let via_out = self.via.port_b_output();
+        let controller = Ieee488Lines {
+            data: !self.pia2.port_b_output(),
+            atn: (via_out & 0x04) == 0,
+            eoi: !self.pia1.ca2_output(),
+            dav: !self.pia2.cb2_output(),
+            nrfd: (via_out & 0x02) == 0,
+            ndac: !self.pia2.ca2_output(),
+            ..Ieee488Lines::default()
+        };
+
+        let lines = self.ieee.update(controller);
+
+        self.pia2.set_port_a_input(!lines.data);
+        self.pia2.set_ca1(!lines.atn);
+        self.pia2.set_cb1(!lines.srq);
+
+        let mut via_in = 0xFF;
+        if self.crtc.vsync() {
+            via_in &= !0x20;
+        }
+        if lines.ndac {
+            via_in &= !0x01;
+        }
+        if lines.nrfd {
+            via_in &= !0x40;
+        }
+        if lines.dav {
+            via_in &= !0x80;
+        }
+        self.via.set_port_b_input(via_in);
+        lines
+    }
+
+    pub fn peek(&mut self, addr: u16) -> Option<u8> {
+        let io = (0xE800..=0xE8FF).contains(&addr)
+            && self.expansion.as_ref().and_then(|e| e.read(addr)).is_none();
+        (!io).then(|| self.read(addr))
+    }
+
+    fn video_ram_index(&self, addr: u16) -> usize {
+        0x8000 + (addr as usize & (self.model.video_ram_size() - 1))
+    }
+
+    fn update_datasette(&mut self) {
+        let via_out = self.via.port_b_output();
+        let write = (via_out & 0x08) != 0;
+        self.datasettes[0].tick(!self.pia1.cb2_output(), write);
+        self.datasettes[1].tick((via_out & 0x10) == 0, write);
+        self.pia1.set_ca1(self.datasettes[0].read_level());
+        self.via.set_control_lines(ViaControlLines {
+            cb1: self.datasettes[1].read_level(),
+            ..ViaControlLines::idle()
+        });
+    }
+}
+
+#[cfg(test)]
+impl PetBus {
+    pub fn with_blank_roms(model: Model) -> Self {
+        let roms = RomData {
+            basic_b000: None,
+            basic_c000: vec![0; 0x1000],
+            basic_d000: vec![0; 0x1000],
+            kernal_f000: vec![0; 0x1000],
+            editor_e000: vec![0; 0x800],
+            char_rom: vec![0; 0x800],
+        };
+        Self::new(roms, model, 0x8000)
+    }
 }
 
 impl CpuBus for PetBus {
     fn read(&mut self, addr: u16) -> u8 {
+        if let Some(val) = self.expansion.as_ref().and_then(|e| e.read(addr)) {
+            return val;
+        }
         match addr {
-            0x0000..=0x7FFF => self.ram[addr as usize],
-            0x8000..=0x87FF => self.ram[addr as usize],
-            0xB000..=0xBFFF => self.roms.basic_b000[(addr & 0x0FFF) as usize],
+            0x0000..=0x7FFF if (addr as usize) < self.ram_size => self.ram[addr as usize],
+            0x0000..=0x7FFF => (addr >> 8) as u8,
+            0x8000..=0x8FFF => self.ram[self.video_ram_index(addr)],
+            0x9000..=0xAFFF => {
+                let socket = &self.option_sockets[(addr as usize >> 12) - 9];
+                socket
+                    .as_ref()
+                    .map_or(0xFF, |s| s.read((addr & 0x0FFF) as usize))
+            }
+            0xB000..=0xBFFF => self
+                .roms
+                .basic_b000
+                .as_ref()
+                .map_or(0xFF, |rom| rom[(addr & 0x0FFF) as usize]),
             0xC000..=0xCFFF => self.roms.basic_c000[(addr & 0x0FFF) as usize],
             0xD000..=0xDFFF => self.roms.basic_d000[(addr & 0x0FFF) as usize],
             0xE000..=0xE7FF => self.roms.editor_e000[(addr & 0x07FF) as usize],
             0xE810..=0xE813 => {
                 let reg = (addr & 0x03) as u8;
-                self.pia.read_register(reg)
+                self.pia1.read_register(reg)
+            }
+            0xE820..=0xE823 => {
+                let reg = (addr & 0x03) as u8;
+                self.pia2.read_register(reg)
             }
             0xE840..=0xE84F => {
                 let reg = (addr & 0x0F) as u8;
                 self.via.read_register(reg)
             }
-            0xE880..=0xE881 => {
+            0xE880..=0xE881 if self.model.has_crtc() => {
                 let reg = (addr & 0x01) as usize;
                 self.crtc.read_register(reg)
             }
@@ -67,18 +200,33 @@ impl CpuBus for PetBus {
     }
 
     fn write(&mut self, addr: u16, val: u8) {
+        if self.expansion.as_mut().is_some_and(|e| e.write(addr, val)) {
+            return;
+        }
         match addr {
-            0x0000..=0x7FFF => self.ram[addr as usize] = val,
-            0x8000..=0x87FF => self.ram[addr as usize] = val,
+            0x0000..=0x7FFF if (addr as usize) < self.ram_size => self.ram[addr as usize] = val,
+            0x8000..=0x8FFF => {
+                let index = self.video_ram_index(addr);
+                self.ram[index] = val;
+            }
+            0x9000..=0xAFFF => {
+                if let Some(socket) = &mut self.option_sockets[(addr as usize >> 12) - 9] {
+                    socket.write((addr & 0x0FFF) as usize, val);
+                }
+            }
             0xE810..=0xE813 => {
                 let reg = (addr & 0x03) as u8;
-                self.pia.write_register(reg, val);
+                self.pia1.write_register(reg, val);
+            }
+            0xE820..=0xE823 => {
+                let reg = (addr & 0x03) as u8;
+                self.pia2.write_register(reg, val);
             }
             0xE840..=0xE84F => {
                 let reg = (addr & 0x0F) as u8;
                 self.via.write_register(reg, val);
             }
-            0xE880..=0xE881 => {
+            0xE880..=0xE881 if self.model.has_crtc() => {
                 let reg = (addr & 0x01) as usize;
                 self.crtc.write_register(reg, val);
             }
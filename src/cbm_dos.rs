@@ -0,0 +1,599 @@
+use crate::ieee488::Ieee488Device;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileType {
+    Del,
+    Seq,
+    Prg,
+    Usr,
+    Rel,
+}
+
+impl FileType {
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code & 0x07 {
+            0 => Some(FileType::Del),
+            1 => Some(FileType::Seq),
+            2 => Some(FileType::Prg),
+            3 => Some(FileType::Usr),
+            4 => Some(FileType::Rel),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> u8 {
+        match self {
+            FileType::Del => 0,
+            FileType::Seq => 1,
+            FileType::Prg => 2,
+            FileType::Usr => 3,
+            FileType::Rel => 4,
+        }
+    }
+
+    pub fn from_letter(letter: u8) -> Option<Self> {
+        match letter {
+            b'D' => Some(FileType::Del),
+            b'S' => Some(FileType::Seq),
+            b'P' => Some(FileType::Prg),
+            b'U' => Some(FileType::Usr),
+            b'L' | b'R' => Some(FileType::Rel),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FileType::Del => "DEL",
+            FileType::Seq => "SEQ",
+            FileType::Prg => "PRG",
+            FileType::Usr => "USR",
+            FileType::Rel => "REL",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccessMode {
+    Read,
+    Write,
+    Append,
+    Modify,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DosError {
+    Ok,
+    FilesScratched,
+    WriteError,
+    WriteProtectOn,
+    SyntaxError,
+    InvalidCommand,
+    NoFileGiven,
+    FileNotFound,
+    FileExists,
+    FileTypeMismatch,
+    DiskFull,
+    DosVersion,
+    DriveNotReady,
+}
+
+impl DosError {
+    pub fn code(self) -> u8 {
+        match self {
+            DosError::Ok => 0,
+            DosError::FilesScratched => 1,
+            DosError::WriteError => 25,
+            DosError::WriteProtectOn => 26,
+            DosError::SyntaxError => 30,
+            DosError::InvalidCommand => 31,
+            DosError::NoFileGiven => 34,
+            DosError::FileNotFound => 62,
+            DosError::FileExists => 63,
+            DosError::FileTypeMismatch => 64,
+            DosError::DiskFull => 72,
+            DosError::DosVersion => 73,
+            DosError::DriveNotReady => 74,
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            DosError::Ok => " OK",
+            DosError::FilesScratched => "FILES SCRATCHED",
+            DosError::WriteError => "WRITE ERROR",
+            DosError::WriteProtectOn => "WRITE PROTECT ON",
+            DosError::SyntaxError | DosError::InvalidCommand | DosError::NoFileGiven => {
+                "SYNTAX ERROR"
+            }
+            DosError::FileNotFound => "FILE NOT FOUND",
+            DosError::FileExists => "FILE EXISTS",
+            DosError::FileTypeMismatch => "FILE TYPE MISMATCH",
+            DosError::DiskFull => "DISK FULL",
+            DosError::DosVersion => "DOS MISMATCH",
+            DosError::DriveNotReady => "DRIVE NOT READY",
+        }
+    }
+}
+
+pub struct ErrorChannel {
+    version: &'static str,
+    message: Vec<u8>,
+    pos: usize,
+}
+
+impl ErrorChannel {
+    pub fn new(version: &'static str) -> Self {
+        let mut channel = Self {
+            version,
+            message: Vec::new(),
+            pos: 0,
+        };
+        channel.reset();
+        channel
+    }
+
+    pub fn reset(&mut self) {
+        self.set_message(DosError::DosVersion.code(), self.version, 0, 0);
+    }
+
+    pub fn set(&mut self, error: DosError, track: u8, sector: u8) {
+        self.set_message(error.code(), error.message(), track, sector);
+    }
+
+    pub fn set_message(&mut self, code: u8, message: &str, track: u8, sector: u8) {
+        self.message = format!("{:02},{},{:02},{:02}\r", code, message, track, sector).into_bytes();
+        self.pos = 0;
+    }
+
+    pub fn read_byte(&mut self) -> (u8, bool) {
+        let byte = self.message[self.pos];
+        self.pos += 1;
+        let last = self.pos >= self.message.len();
+        if last {
+            self.set(DosError::Ok, 0, 0);
+        }
+        (byte, last)
+    }
+}
+
+pub struct FileSpec {
+    pub replace: bool,
+    pub drive: Option<u8>,
+    pub name: Vec<u8>,
+    pub file_type: Option<FileType>,
+    pub mode: Option<AccessMode>,
+}
+
+pub fn parse_file_spec(raw: &[u8]) -> FileSpec {
+    let mut spec = FileSpec {
+        replace: false,
+        drive: None,
+        name: Vec::new(),
+        file_type: None,
+        mode: None,
+    };
+
+    let mut rest = raw;
+    if let Some(stripped) = rest.strip_prefix(b"@") {
+        spec.replace = true;
+        rest = stripped;
+    }
+
+    if let Some(colon) = rest.iter().position(|&b| b == b':') {
+        let prefix = &rest[..colon];
+        spec.drive = prefix.iter().find(|b| b.is_ascii_digit()).map(|b| b - b'0');
+        rest = &rest[colon + 1..];
+    }
+
+    let mut parts = rest.split(|&b| b == b',');
+    spec.name = parts.next().unwrap_or_default().to_vec();
+    for part in parts {
+        match part.first() {
+            Some(b'R') => spec.mode = Some(AccessMode::Read),
+            Some(b'W') => spec.mode = Some(AccessMode::Write),
+            Some(b'A') => spec.mode = Some(AccessMode::Append),
+            Some(b'M') => spec.mode = Some(AccessMode::Modify),
+            Some(&letter) if spec.file_type.is_none() => {
+                spec.file_type = FileType::from_letter(letter);
+            }
+            _ => {}
+        }
+    }
+    spec
+}
+
+pub fn matches_pattern(pattern: &[u8], name: &[u8]) -> bool {
+    let mut i = 0;
+    for &p in pattern {
+        match p {
+            b'*' => return true,
+            b'?' => {
+                if i >= name.len() {
+                    return false;
+                }
+            }
+            _ => {
+                if name.get(i) != Some(&p) {
+                    return false;
+                }
+            }
+        }
+        i += 1;
+    }
+    i == name.len()
+}
+
+pub struct DirectoryEntry {
+    pub name: Vec<u8>,
+    pub file_type: FileType,
+    pub blocks: u16,
+    pub closed: bool,
+    pub locked: bool,
+}
+
+pub struct DirectoryListing {
+    data: Vec<u8>,
+    next_line: u16,
+}
+
+impl DirectoryListing {
+    pub fn new() -> Self {
+        Self {
+            data: vec![0x01, 0x04],
+            next_line: 0x0401,
+        }
+    }
+
+    pub fn add_header(&mut self, drive: u8, disk_name: &[u8], disk_id: &[u8]) {
+        let mut text = vec![0x12, b'"'];
+        text.extend(padded(disk_name, 16));
+        text.extend(b"\" ");
+        text.extend(padded(disk_id, 5));
+        self.add_line(drive as u16, &text);
+    }
+
+    pub fn add_entry(&mut self, entry: &DirectoryEntry) {
+        let mut text = Vec::new();
+        let digits = entry.blocks.to_string().len();
+        text.extend(std::iter::repeat_n(b' ', 4usize.saturating_sub(digits)));
+        text.push(b'"');
+        text.extend(&entry.name);
+        text.push(b'"');
+        text.extend(std::iter::repeat_n(
+            b' ',
+            16usize.saturating_sub(entry.name.len()),
+        ));
+        text.push(if entry.closed { b' ' } else { b'*' });
+        text.extend(entry.file_type.name().as_bytes());
+        text.push(if entry.locked { b'<' } else { b' ' });
+        self.add_line(entry.blocks, &text);
+    }
+
+    pub fn add_blocks_free(&mut self, blocks_free: u16) {
+        let mut text = b"BLOCKS FREE.".to_vec();
+        text.extend(std::iter::repeat_n(b' ', 13));
+        self.add_line(blocks_free, &text);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.data.extend([0x00, 0x00]);
+        self.data
+    }
+
+    fn add_line(&mut self, number: u16, text: &[u8]) {
+        self.next_line += (text.len() + 5) as u16;
+        self.data.extend(self.next_line.to_le_bytes());
+        self.data.extend(number.to_le_bytes());
+        self.data.extend(text);
+        self.data.push(0x00);
+    }
+}
+
+fn padded(bytes: &[u8], len: usize) -> Vec<u8> {
+    let mut result: Vec<u8> = bytes
+        .iter()
+        .take(len)
+        .map(|&b| if b == 0xA0 { b' ' } else { b })
+        .collect();
+    result.resize(len, b' ');
+    result
+}
+
+pub fn petscii_name(raw: &[u8]) -> String {
+    let end = raw
+        .iter()
+        .rposition(|&b| b != 0x20 && b != 0xA0 && b != 0)
+        .map_or(0, |i| i + 1);
+    raw[..end]
+        .iter()
+        .map(|&b| match b {
+            0x20..=0x5F => b as char,
+            0xC1..=0xDA => (b - 0x80) as char,
+            _ => '?',
+        })
+        .collect()
+}
+
+enum Channel<T> {
+    Read { data: Vec<u8>, pos: usize },
+    Write { target: T, data: Vec<u8> },
+}
+
+pub trait DosStorage {
+    type Target;
+
+    fn directory(&self, argument: &[u8]) -> Result<Vec<u8>, DosError>;
+    fn read_file(
+        &self,
+        drive: u8,
+        name: &[u8],
+        file_type: Option<FileType>,
+    ) -> Result<Vec<u8>, DosError>;
+    fn create_file(
+        &mut self,
+        drive: u8,
+        name: &[u8],
+        file_type: FileType,
+        mode: AccessMode,
+        replace: bool,
+    ) -> Result<(Self::Target, Vec<u8>), DosError>;
+    fn write_file(&mut self, target: Self::Target, data: &[u8]) -> Result<(), DosError>;
+    fn scratch(&mut self, drive: u8, pattern: &[u8]) -> Result<u8, DosError>;
+    fn rename(&mut self, drive: u8, old: &[u8], new: &[u8]) -> Result<(), DosError>;
+    fn command(&mut self, drive: u8, command: &[u8], argument: &[u8]) -> Result<(), DosError>;
+
+    fn mount(&mut self, _drive: u8, _path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        Err("Device does not accept media".into())
+    }
+}
+
+pub struct DosDevice<S: DosStorage> {
+    device_number: u8,
+    pub storage: S,
+    channels: [Option<Channel<S::Target>>; 15],
+    error: ErrorChannel,
+    command: Vec<u8>,
+}
+
+impl<S: DosStorage> DosDevice<S> {
+    pub fn with_storage(device_number: u8, version: &'static str, storage: S) -> Self {
+        Self {
+            device_number,
+            storage,
+            channels: Default::default(),
+            error: ErrorChannel::new(version),
+            command: Vec::new(),
+        }
+    }
+
+    fn close_all(&mut self) {
+        for sa in 0..self.channels.len() as u8 {
+            self.close_channel(sa);
+        }
+    }
+
+    fn open_channel(&mut self, secondary: u8, name: &[u8]) -> Result<Channel<S::Target>, DosError> {
+        if let Some(argument) = name.strip_prefix(b"$") {
+            let data = self.storage.directory(argument)?;
+            return Ok(Channel::Read { data, pos: 0 });
+        }
+
+        let spec = parse_file_spec(name);
+        if spec.name.is_empty() {
+            return Err(DosError::NoFileGiven);
+        }
+        let drive = spec.drive.unwrap_or(0);
+
+        let mode = match (secondary, spec.mode) {
+            (_, Some(mode)) => mode,
+            (1, None) => AccessMode::Write,
+            _ => AccessMode::Read,
+        };
+
+        match mode {
+            AccessMode::Read => {
+                let file_type = spec.file_type.or(match secondary {
+                    0 => Some(FileType::Prg),
+                    _ => None,
+                });
+                let data = self.storage.read_file(drive, &spec.name, file_type)?;
+                Ok(Channel::Read { data, pos: 0 })
+            }
+            AccessMode::Write | AccessMode::Append => {
+                let file_type = spec.file_type.unwrap_or(match secondary {
+                    1 => FileType::Prg,
+                    _ => FileType::Seq,
+                });
+                let (target, data) =
+                    self.storage
+                        .create_file(drive, &spec.name, file_type, mode, spec.replace)?;
+                Ok(Channel::Write { target, data })
+            }
+            AccessMode::Modify => Err(DosError::FileTypeMismatch),
+        }
+    }
+
+    fn close_channel(&mut self, secondary: u8) {
+        let Some(Channel::Write { target, data }) = self
+            .channels
+            .get_mut(secondary as usize)
+            .and_then(|c| c.take())
+        else {
+            return;
+        };
+        if let Err(error) = self.storage.write_file(target, &data) {
+            self.error.set(error, 0, 0);
+        }
+    }
+
+    fn execute_command(&mut self) {
+        let mut command = std::mem::take(&mut self.command);
+        while command.last() == Some(&b'\r') {
+            command.pop();
+        }
+        if command.is_empty() {
+            return;
+        }
+
+        let result = self.run_command(&command);
+        match result {
+            Ok(Some((error, track))) => self.error.set(error, track, 0),
+            Ok(None) => {}
+            Err(error) => self.error.set(error, 0, 0),
+        }
+    }
+
+    fn run_command(&mut self, command: &[u8]) -> Result<Option<(DosError, u8)>, DosError> {
+        let argument = command
+            .iter()
+            .position(|&b| b == b':')
+            .map(|colon| &command[colon + 1..])
+            .unwrap_or_default();
+        let drive = command
+            .iter()
+            .skip(1)
+            .take_while(|&&b| b != b':' && b != b'=')
+            .find(|b| b.is_ascii_digit())
+            .map_or(0, |b| b - b'0');
+
+        match command[0] {
+            b'U' if matches!(command.get(1), Some(b'J') | Some(b':') | Some(b'I')) => {
+                self.close_all();
+                self.error.reset();
+                Ok(None)
+            }
+            b'S' => {
+                let mut count = 0u8;
+                for pattern in argument.split(|&b| b == b',') {
+                    let spec = parse_file_spec(pattern);
+                    let scratched = self
+                        .storage
+                        .scratch(spec.drive.unwrap_or(drive), &spec.name)?;
+                    count = count.saturating_add(scratched);
+                }
+                Ok(Some((DosError::FilesScratched, count)))
+            }
+            b'R' => {
+                let equals = argument
+                    .iter()
+                    .position(|&b| b == b'=')
+                    .ok_or(DosError::SyntaxError)?;
+                let new = &argument[..equals];
+                let old = parse_file_spec(&argument[equals + 1..]).name;
+                self.storage.rename(drive, &old, new)?;
+                Ok(Some((DosError::Ok, 0)))
+            }
+            _ => {
+                self.storage.command(drive, command, argument)?;
+                Ok(Some((DosError::Ok, 0)))
+            }
+        }
+    }
+}
+
+impl<S: DosStorage> Ieee488Device for DosDevice<S> {
+    fn device_number(&self) -> u8 {
+        self.device_number
+    }
+
+    fn open(&mut self, secondary: u8, name: &[u8]) {
+        if secondary == 15 {
+            self.command = name.to_vec();
+            self.execute_command();
+            return;
+        }
+
+        self.close_channel(secondary);
+        match self.open_channel(secondary, name) {
+            Ok(channel) => {
+                self.channels[secondary as usize] = Some(channel);
+                self.error.set(DosError::Ok, 0, 0);
+            }
+            Err(error) => self.error.set(error, 0, 0),
+        }
+    }
+
+    fn close(&mut self, secondary: u8) {
+        if secondary == 15 {
+            self.close_all();
+        } else {
+            self.close_channel(secondary);
+        }
+    }
+
+    fn write_byte(&mut self, secondary: u8, byte: u8) {
+        if secondary == 15 {
+            self.command.push(byte);
+            return;
+        }
+        if let Some(Some(Channel::Write { data, .. })) = self.channels.get_mut(secondary as usize) {
+            data.push(byte);
+        }
+    }
+
+    fn read_byte(&mut self, secondary: u8) -> Option<(u8, bool)> {
+        if secondary == 15 {
+            return Some(self.error.read_byte());
+        }
+        match self.channels.get_mut(secondary as usize) {
+            Some(Some(Channel::Read { data, pos })) if *pos < data.len() => {
+                let byte = data[*pos];
+                *pos += 1;
+                Some((byte, *pos == data.len()))
+            }
+            _ => None,
+        }
+    }
+
+    fn unlisten(&mut self, secondary: u8) {
+        if secondary == 15 {
+            self.execute_command();
+        }
+    }
+
+    fn mount(&mut self, drive: u8, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.close_all();
+        self.storage.mount(drive, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_spec() {
+        let spec = parse_file_spec(b"@0:GAME,P,W");
+        assert!(spec.replace);
+        assert_eq!(spec.drive, Some(0));
+        assert_eq!(spec.name, b"GAME");
+        assert_eq!(spec.file_type, Some(FileType::Prg));
+        assert_eq!(spec.mode, Some(AccessMode::Write));
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern(b"CHESS", b"CHESS"));
+        assert!(matches_pattern(b"CH*", b"CHESS"));
+        assert!(matches_pattern(b"CH?SS", b"CHESS"));
+        assert!(!matches_pattern(b"CHES", b"CHESS"));
+        assert!(matches_pattern(b"*", b""));
+    }
+
+    #[test]
+    fn test_error_channel() {
+        let mut channel = ErrorChannel::new("CBM DOS V2.6 2031");
+        channel.set(DosError::FileNotFound, 0, 0);
+        let mut message = Vec::new();
+        loop {
+            let (byte, last) = channel.read_byte();
+            message.push(byte);
+            if last {
+                break;
+            }
+        }
+        assert_eq!(message, b"62,FILE NOT FOUND,00,00\r");
+    }
+}
@@ -0,0 +1,333 @@
+use crate::disk_drive::DriveModel;
+use crate::disk_image::DiskFormat;
+use crate::machine::Model;
+use crate::rom_loader::OptionSocket;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+pub const DEFAULT_CONFIG: &str = "pet.toml";
+const DEFAULT_SOFTWARE_DIR: &str = "./software";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RomPaths {
+    pub basic_b000: Option<String>,
+    pub basic_c000: Option<String>,
+    pub basic_d000: Option<String>,
+    pub kernal: Option<String>,
+    pub editor: Option<String>,
+    pub characters: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DriveConfig {
+    pub unit: u8,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub path: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub scale: Option<usize>,
+    pub color: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub model: Option<String>,
+    pub ram_kb: Option<usize>,
+    pub keyboard: Option<String>,
+    pub software_dir: Option<String>,
+    pub kernal_traps: Option<bool>,
+    pub roms: Option<RomPaths>,
+    pub rom_dirs: Option<Vec<String>>,
+    pub rom_9000: Option<String>,
+    pub rom_a000: Option<String>,
+    pub ram_9000: Option<bool>,
+    pub ram_a000: Option<bool>,
+    pub drives: Option<Vec<DriveConfig>>,
+    pub display: Option<DisplayConfig>,
+}
+
+pub struct Display {
+    pub scale: usize,
+    pub color: (u8, u8, u8),
+}
+
+pub struct MachineConfig {
+    pub model: Model,
+    pub ram_size: usize,
+    pub business_keyboard: bool,
+    pub software_dir: String,
+    pub kernal_traps: bool,
+    pub roms: RomPaths,
+    pub rom_dirs: Vec<String>,
+    pub option_sockets: [Option<OptionSocket>; 2],
+    pub socket_ram: [bool; 2],
+    pub drives: Vec<DriveConfig>,
+    pub display: Display,
+}
+
+pub fn load_profile(path: &Path, name: Option<&str>) -> Result<Profile, Box<dyn Error>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read config {}: {}", path.display(), e))?;
+    let profile = parse_profile(&text, name).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(profile.relative_to(path.parent().unwrap_or(Path::new(""))))
+}
+
+fn parse_profile(text: &str, name: Option<&str>) -> Result<Profile, Box<dyn Error>> {
+    let mut config: ConfigFile = toml::from_str(text)?;
+    let Some(name) = name.or(config.default_profile.as_deref()) else {
+        return Ok(Profile::default());
+    };
+    config.profiles.remove(name).ok_or_else(|| {
+        let mut names: Vec<_> = config.profiles.keys().cloned().collect();
+        names.sort();
+        format!(
+            "Unknown profile '{}' (available: {})",
+            name,
+            names.join(", ")
+        )
+        .into()
+    })
+}
+
+impl Profile {
+    fn relative_to(mut self, base: &Path) -> Profile {
+        let rebase = |path: &mut String| *path = base.join(&*path).to_string_lossy().into_owned();
+        self.software_dir.iter_mut().for_each(rebase);
+        self.rom_dirs.iter_mut().flatten().for_each(rebase);
+        self.rom_9000.iter_mut().for_each(rebase);
+        self.rom_a000.iter_mut().for_each(rebase);
+        if let Some(roms) = &mut self.roms {
+            [
+                &mut roms.basic_b000,
+                &mut roms.basic_c000,
+                &mut roms.basic_d000,
+                &mut roms.kernal,
+                &mut roms.editor,
+                &mut roms.characters,
+            ]
+            .into_iter()
+            .flatten()
+            .for_each(rebase);
+        }
+        self.drives
+            .iter_mut()
+            .flatten()
+            .filter_map(|drive| drive.path.as_mut())
+            .for_each(rebase);
+        self
+    }
+
+    pub fn merge(self, overrides: Profile) -> Profile {
+        Profile {
+            model: overrides.model.or(self.model),
+            ram_kb: overrides.ram_kb.or(self.ram_kb),
+            keyboard: overrides.keyboard.or(self.keyboard),
+            software_dir: overrides.software_dir.or(self.software_dir),
+            kernal_traps: overrides.kernal_traps.or(self.kernal_traps),
+            roms: overrides.roms.or(self.roms),
+            rom_dirs: overrides.rom_dirs.or(self.rom_dirs),
+            rom_9000: overrides.rom_9000.or(self.rom_9000),
+            rom_a000: overrides.rom_a000.or(self.rom_a000),
+            ram_9000: overrides.ram_9000.or(self.ram_9000),
+            ram_a000: overrides.ram_a000.or(self.ram_a000),
+            drives: overrides.drives.or(self.drives),
+            display: overrides.display.or(self.display),
+        }
+    }
+
+    pub fn resolve(self) -> Result<MachineConfig, Box<dyn Error>> {
+        let model = match &self.model {
+            Some(name) => Model::from_name(name).ok_or(format!("Unknown model: {}", name))?,
+            None => Model::Pet4032,
+        };
+        let ram_size = match self.ram_kb {
+            None => model.ram_size(),
+            Some(kb @ (8 | 16 | 32)) => kb * 1024,
+            Some(kb) => return Err(format!("Unsupported RAM size: {}K", kb).into()),
+        };
+        let business_keyboard = match self.keyboard.as_deref() {
+            None => model.business_keyboard(),
+            Some("graphics") => false,
+            Some("business") => true,
+            Some(other) => return Err(format!("Unknown keyboard layout: {}", other).into()),
+        };
+        let software_dir = self
+            .software_dir
+            .unwrap_or_else(|| DEFAULT_SOFTWARE_DIR.to_string());
+        let sockets = [
+            (self.rom_9000.as_deref(), self.ram_9000),
+            (self.rom_a000.as_deref(), self.ram_a000),
+        ];
+        let jumpers = model.socket_ram();
+        let socket_ram = sockets.map(|(rom, ram)| jumpers && rom.is_none() && ram.unwrap_or(true));
+        let option_sockets = [
+            option_socket(sockets[0].0, sockets[0].1.filter(|_| !jumpers))?,
+            option_socket(sockets[1].0, sockets[1].1.filter(|_| !jumpers))?,
+        ];
+        let drives = self.drives.unwrap_or_else(|| {
+            [(8, "2031"), (9, "8250"), (10, "host")]
+                .into_iter()
+                .map(|(unit, kind)| DriveConfig {
+                    unit,
+                    kind: kind.to_string(),
+                    path: None,
+                })
+                .collect()
+        });
+        let display = self
+            .display
+            .unwrap_or(DisplayConfig {
+                scale: None,
+                color: None,
+            })
+            .resolve()?;
+        Ok(MachineConfig {
+            model,
+            ram_size,
+            business_keyboard,
+            software_dir,
+            kernal_traps: self.kernal_traps.unwrap_or(model.basic_version() == 4),
+            roms: self.roms.unwrap_or_default(),
+            rom_dirs: self.rom_dirs.unwrap_or_default(),
+            option_sockets,
+            socket_ram,
+            drives,
+            display,
+        })
+    }
+}
+
+impl MachineConfig {
+    pub fn drive_for(&self, format: DiskFormat) -> Option<u8> {
+        self.drives
+            .iter()
+            .find(|drive| {
+                DriveModel::from_name(&drive.kind).is_some_and(|model| model.accepts(format))
+            })
+            .map(|drive| drive.unit)
+    }
+}
+
+impl DisplayConfig {
+    fn resolve(self) -> Result<Display, Box<dyn Error>> {
+        let scale = self.scale.unwrap_or(1);
+        if !(1..=4).contains(&scale) {
+            return Err(format!("Display scale must be 1-4, not {}", scale).into());
+        }
+        let color = match self.color.as_deref().unwrap_or("green") {
+            "green" => (50, 255, 50),
+            "amber" => (255, 176, 0),
+            "white" => (230, 230, 230),
+            other => return Err(format!("Unknown display color: {}", other).into()),
+        };
+        Ok(Display { scale, color })
+    }
+}
+
+fn option_socket(
+    rom: Option<&str>,
+    ram: Option<bool>,
+) -> Result<Option<OptionSocket>, Box<dyn Error>> {
+    match (rom, ram) {
+        (Some(path), _) => Ok(Some(OptionSocket::rom(Path::new(path))?)),
+        (None, Some(true)) => Ok(Some(OptionSocket::ram())),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+default_profile = "small"
+
+[profiles.small]
+model = "2001"
+ram_kb = 8
+software_dir = "/tmp/pet"
+
+[profiles.business]
+model = "8032"
+keyboard = "graphics"
+ram_a000 = true
+drives = [{ unit = 8, type = "8250", path = "work.d80" }]
+display = { color = "amber", scale = 2 }
+"#;
+
+    #[test]
+    fn test_profiles() {
+        let small = parse_profile(CONFIG, None).unwrap().resolve().unwrap();
+        assert_eq!(small.model, Model::Pet2001);
+        assert_eq!(small.ram_size, 0x2000);
+        assert_eq!(small.software_dir, "/tmp/pet");
+        assert!(!small.kernal_traps);
+        assert_eq!(small.drives.len(), 3);
+        assert_eq!(small.drive_for(DiskFormat::D64), Some(8));
+        assert_eq!(small.drive_for(DiskFormat::D82), Some(9));
+
+        let overrides = Profile {
+            ram_kb: Some(16),
+            ..Profile::default()
+        };
+        let business = parse_profile(CONFIG, Some("business"))
+            .unwrap()
+            .merge(overrides)
+            .resolve()
+            .unwrap();
+        assert_eq!(business.model, Model::Pet8032);
+        assert_eq!(business.ram_size, 0x4000);
+        assert!(!business.business_keyboard);
+        assert!(business.option_sockets[0].is_none());
+        assert!(business.option_sockets[1].is_some());
+        assert_eq!(business.socket_ram, [false, false]);
+        assert_eq!(business.drives[0].path.as_deref(), Some("work.d80"));
+        assert_eq!(business.display.color, (255, 176, 0));
+        assert_eq!(business.drive_for(DiskFormat::D80), Some(8));
+        assert_eq!(business.drive_for(DiskFormat::D64), None);
+
+        let workstation = Profile {
+            model: Some("8296".to_string()),
+            ram_a000: Some(false),
+            ..Profile::default()
+        }
+        .resolve()
+        .unwrap();
+        assert_eq!(workstation.socket_ram, [true, false]);
+        assert!(workstation.option_sockets.iter().all(Option::is_none));
+
+        assert!(parse_profile(CONFIG, Some("missing")).is_err());
+        assert!(parse_profile("[profiles.x]\nmodle = \"4032\"", Some("x")).is_err());
+    }
+
+    #[test]
+    fn test_paths_relative_to_config() {
+        let base = Path::new("/home/me/pet");
+        let small = parse_profile(CONFIG, None).unwrap().relative_to(base);
+        assert_eq!(small.software_dir.as_deref(), Some("/tmp/pet"));
+
+        let business = parse_profile(CONFIG, Some("business"))
+            .unwrap()
+            .relative_to(base);
+        let drives = business.drives.unwrap();
+        assert_eq!(drives[0].path.as_deref(), Some("/home/me/pet/work.d80"));
+    }
+}
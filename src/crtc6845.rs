@@ -1,3 +1,5 @@
+use crate::machine::Model;
+
 pub struct Crtc6845 {
     pub registers: [u8; 18],
     pub selected_register: usize,
@@ -5,6 +7,14 @@ pub struct Crtc6845 {
     pub cursor_address: u16,
     pub cursor_start_reg: u8,
     pub cursor_end_reg: u8,
+    column: u8,
+    scanline: u8,
+    row: u8,
+    adjust_line: Option<u8>,
+    vsync_lines: u8,
+    frame_started: bool,
+    frame_count: u32,
+    chars_per_clock: usize,
 }
 
 impl Crtc6845 {
@@ -16,9 +26,107 @@ impl Crtc6845 {
             cursor_address: 0,
             cursor_start_reg: 0,
             cursor_end_reg: 0,
+            column: 0,
+            scanline: 0,
+            row: 0,
+            adjust_line: None,
+            vsync_lines: 0,
+            frame_started: false,
+            frame_count: 0,
+            chars_per_clock: 1,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if self.column < self.registers[0] {
+            self.column += 1;
+            return;
+        }
+        self.column = 0;
+        self.vsync_lines = self.vsync_lines.saturating_sub(1);
+
+        let max_scanline = self.registers[9] & 0x1F;
+        let total_rows = self.registers[4] & 0x7F;
+        let adjust_lines = self.registers[5] & 0x1F;
+        if let Some(line) = self.adjust_line {
+            if line + 1 >= adjust_lines {
+                self.start_frame();
+            } else {
+                self.adjust_line = Some(line + 1);
+            }
+        } else if self.scanline < max_scanline {
+            self.scanline += 1;
+        } else if self.row < total_rows {
+            self.scanline = 0;
+            self.row += 1;
+            if self.row == self.registers[7] & 0x7F {
+                self.start_vsync();
+            }
+        } else if adjust_lines > 0 {
+            self.adjust_line = Some(0);
+        } else {
+            self.start_frame();
         }
     }
 
+    fn start_frame(&mut self) {
+        self.row = 0;
+        self.scanline = 0;
+        self.adjust_line = None;
+        self.frame_started = true;
+        self.frame_count = self.frame_count.wrapping_add(1);
+        if self.registers[7] & 0x7F == 0 {
+            self.start_vsync();
+        }
+    }
+
+    fn start_vsync(&mut self) {
+        self.vsync_lines = match self.registers[3] >> 4 {
+            0 => 16,
+            width => width,
+        };
+    }
+
+    pub fn vsync(&self) -> bool {
+        self.vsync_lines > 0
+    }
+
+    pub fn chars_per_clock(&self) -> usize {
+        self.chars_per_clock
+    }
+
+    pub fn displayed_columns(&self) -> usize {
+        self.registers[1] as usize * self.chars_per_clock
+    }
+
+    pub fn displayed_rows(&self) -> usize {
+        (self.registers[6] & 0x7F) as usize
+    }
+
+    pub fn scanlines_per_row(&self) -> usize {
+        (self.registers[9] & 0x1F) as usize + 1
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        match (self.cursor_start_reg >> 5) & 0x03 {
+            0 => true,
+            1 => false,
+            2 => (self.frame_count & 0x08) == 0,
+            _ => (self.frame_count & 0x10) == 0,
+        }
+    }
+
+    pub fn cursor_scanlines(&self) -> (usize, usize) {
+        (
+            (self.cursor_start_reg & 0x1F) as usize,
+            (self.cursor_end_reg & 0x1F) as usize,
+        )
+    }
+
+    pub fn take_frame_started(&mut self) -> bool {
+        std::mem::take(&mut self.frame_started)
+    }
+
     pub fn write_register(&mut self, register_select: usize, data: u8) {
         match register_select {
             0 => {
@@ -52,27 +160,26 @@ impl Crtc6845 {
         }
     }
 
-    pub fn screen_start_address(&self) -> u16 {
-        0x8000 + self.screen_start_address
-    }
-
-    pub fn init_pet4032_screen(&mut self) {
-        self.registers[0] = 0x31;
-        self.registers[1] = 0x27;
-        self.registers[2] = 0x29;
-        self.registers[3] = 0x0A;
-        self.registers[4] = 0x1F;
-        self.registers[5] = 0x00;
-        self.registers[6] = 0x18;
-        self.registers[7] = 0x1E;
-        self.registers[8] = 0x00;
-        self.registers[9] = 0x07;
-        self.registers[10] = 0x00;
-        self.registers[11] = 0x00;
-        self.registers[12] = 0x00;
-        self.registers[13] = 0x00;
-        self.registers[14] = 0x00;
-        self.registers[15] = 0x00;
+    pub fn init_screen(&mut self, model: Model) {
+        self.chars_per_clock = model.chars_per_clock();
+        let registers: [u8; 16] = match model {
+            // Discrete video: 64 cycles per line, 260 lines, no cursor.
+            Model::Pet2001 | Model::Pet3032 => [
+                0x3F, 0x28, 0x29, 0x40, 0x1F, 0x04, 0x19, 0x1C, 0x00, 0x07, 0x20, 0x00, 0x00, 0x00,
+                0x00, 0x00,
+            ],
+            // The editor ROM's tables: graphics mode on the 4032, text
+            // mode on the 80-column machines.
+            Model::Pet4032 => [
+                0x31, 0x28, 0x29, 0x0F, 0x28, 0x05, 0x19, 0x21, 0x00, 0x07, 0x00, 0x00, 0x10, 0x00,
+                0x00, 0x00,
+            ],
+            Model::Pet8032 | Model::Pet8096 | Model::Pet8296 => [
+                0x31, 0x28, 0x29, 0x0F, 0x20, 0x03, 0x19, 0x1D, 0x00, 0x09, 0x00, 0x00, 0x10, 0x00,
+                0x00, 0x00,
+            ],
+        };
+        self.registers[..16].copy_from_slice(&registers);
 
         self.screen_start_address = (self.registers[12] as u16) << 8 | (self.registers[13] as u16);
         self.cursor_address = (self.registers[14] as u16) << 8 | (self.registers[15] as u16);
@@ -88,3 +195,114 @@ impl Crtc6845 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(crtc: &mut Crtc6845, values: &[u8]) {
+        for (reg, &value) in values.iter().enumerate() {
+            crtc.write_register(0, reg as u8);
+            crtc.write_register(1, value);
+        }
+    }
+
+    #[test]
+    fn test_vsync_follows_registers() {
+        let mut crtc = Crtc6845::new();
+        program(
+            &mut crtc,
+            &[0x31, 0x28, 0x29, 0x0F, 0x20, 0x03, 0x19, 0x1D, 0x00, 0x09],
+        );
+        assert_eq!(crtc.displayed_columns(), 40);
+        assert_eq!(crtc.displayed_rows(), 25);
+        assert_eq!(crtc.scanlines_per_row(), 10);
+
+        let mut frames = 0;
+        let mut vsync_starts = Vec::new();
+        let mut vsync = crtc.vsync();
+        for cycle in 0..3 * 16650 {
+            crtc.tick();
+            if crtc.take_frame_started() {
+                frames += 1;
+            }
+            if crtc.vsync() && !vsync {
+                vsync_starts.push(cycle);
+            }
+            vsync = crtc.vsync();
+        }
+        assert_eq!(frames, 3);
+        assert_eq!(vsync_starts.len(), 3);
+        assert_eq!(vsync_starts[1] - vsync_starts[0], 16650);
+        assert!(!crtc.vsync());
+    }
+
+    #[test]
+    fn test_discrete_video_timing() {
+        let mut crtc = Crtc6845::new();
+        crtc.init_screen(Model::Pet2001);
+        assert_eq!(crtc.displayed_columns(), 40);
+        assert_eq!(crtc.displayed_rows(), 25);
+        assert!(!crtc.cursor_visible());
+
+        let mut frame_starts = Vec::new();
+        for cycle in 0..2 * 16640 {
+            crtc.tick();
+            if crtc.take_frame_started() {
+                frame_starts.push(cycle);
+            }
+        }
+        assert_eq!(frame_starts.len(), 2);
+        assert_eq!(frame_starts[1] - frame_starts[0], 16640);
+    }
+
+    #[test]
+    fn test_power_on_geometry() {
+        let mut crtc = Crtc6845::new();
+        crtc.init_screen(Model::Pet4032);
+        assert_eq!(crtc.displayed_columns(), 40);
+        assert_eq!(crtc.displayed_rows(), 25);
+        assert_eq!(crtc.scanlines_per_row(), 8);
+
+        crtc.init_screen(Model::Pet8032);
+        assert_eq!(crtc.displayed_columns(), 80);
+        assert_eq!(crtc.displayed_rows(), 25);
+        assert_eq!(crtc.scanlines_per_row(), 10);
+
+        let mut frame_starts = Vec::new();
+        for cycle in 0..2 * 16650 {
+            crtc.tick();
+            if crtc.take_frame_started() {
+                frame_starts.push(cycle);
+            }
+        }
+        assert_eq!(frame_starts[1] - frame_starts[0], 16650);
+    }
+
+    #[test]
+    fn test_cursor_blink_modes() {
+        let mut crtc = Crtc6845::new();
+        program(&mut crtc, &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x05, 0x07]);
+        assert_eq!(crtc.cursor_scanlines(), (5, 7));
+        let mut visible = Vec::new();
+        for _ in 0..32 {
+            visible.push(crtc.cursor_visible());
+            crtc.tick();
+        }
+        assert_eq!(visible, [true; 32]);
+
+        crtc.write_register(0, 10);
+        crtc.write_register(1, 0x20);
+        assert!(!crtc.cursor_visible());
+
+        crtc.write_register(1, 0x40);
+        let mut visible = Vec::new();
+        for _ in 0..32 {
+            visible.push(crtc.cursor_visible());
+            crtc.tick();
+        }
+        assert_eq!(visible.iter().filter(|&&v| v).count(), 16);
+        assert_ne!(visible[0], visible[8]);
+        assert_eq!(visible[0], visible[16]);
+    }
+}
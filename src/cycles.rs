@@ -0,0 +1,87 @@
+#[rustfmt::skip]
+const BASE_CYCLES: [u8; 256] = [
+    7, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    6, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+    2, 6, 2, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5,
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+    2, 5, 2, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4,
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+];
+
+pub const INTERRUPT_CYCLES: u32 = 7;
+
+pub fn instruction_cycles(opcode: u8, pc_before: u16, pc_after: u16) -> u32 {
+    let mut cycles = BASE_CYCLES[opcode as usize] as u32;
+    let is_branch = (opcode & 0x1F) == 0x10;
+    let next = pc_before.wrapping_add(2);
+    if is_branch && pc_after != next {
+        cycles += 1;
+        if (pc_after & 0xFF00) != (next & 0xFF00) {
+            cycles += 1;
+        }
+    }
+    cycles
+}
+
+pub fn page_penalty(opcode: u8, pc: u16, x: u8, y: u8, mut read: impl FnMut(u16) -> u8) -> u32 {
+    let operand = pc.wrapping_add(1);
+    let absolute = (operand, operand.wrapping_add(1));
+    let (pointer, index) = match opcode {
+        0x1C | 0x1D | 0x3C | 0x3D | 0x5C | 0x5D | 0x7C | 0x7D | 0xBC | 0xBD | 0xDC | 0xDD
+        | 0xFC | 0xFD => (absolute, x),
+        0x19 | 0x39 | 0x59 | 0x79 | 0xB9 | 0xBB | 0xBE | 0xBF | 0xD9 | 0xF9 => (absolute, y),
+        0x11 | 0x31 | 0x51 | 0x71 | 0xB1 | 0xB3 | 0xD1 | 0xF1 => {
+            let zero_page = read(operand);
+            ((zero_page as u16, zero_page.wrapping_add(1) as u16), y)
+        }
+        _ => return 0,
+    };
+    let base = u16::from_le_bytes([read(pointer.0), read(pointer.1)]);
+    ((base & 0xFF) + index as u16 > 0xFF) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction_cycles() {
+        assert_eq!(instruction_cycles(0xEA, 0x1000, 0x1001), 2);
+        assert_eq!(instruction_cycles(0xAD, 0x1000, 0x1003), 4);
+        assert_eq!(instruction_cycles(0x20, 0x1000, 0x2000), 6);
+        assert_eq!(instruction_cycles(0x00, 0x1000, 0xE000), 7);
+
+        assert_eq!(instruction_cycles(0xD0, 0x1000, 0x1002), 2);
+        assert_eq!(instruction_cycles(0xD0, 0x1000, 0x1010), 3);
+        assert_eq!(instruction_cycles(0xD0, 0x10F0, 0x1100), 4);
+        assert_eq!(instruction_cycles(0x10, 0x1000, 0x0FF0), 4);
+    }
+
+    #[test]
+    fn test_page_penalty() {
+        let memory = |addr: u16| match addr {
+            0x1001 => 0xF0,
+            0x1002 => 0x20,
+            0x2001 => 0x20,
+            0x0020 => 0x80,
+            0x0021 => 0x30,
+            _ => 0,
+        };
+        assert_eq!(page_penalty(0xBD, 0x1000, 0x0F, 0, memory), 0);
+        assert_eq!(page_penalty(0xBD, 0x1000, 0x10, 0, memory), 1);
+        assert_eq!(page_penalty(0x9D, 0x1000, 0x10, 0, memory), 0);
+        assert_eq!(page_penalty(0xB9, 0x1000, 0, 0x20, memory), 1);
+        assert_eq!(page_penalty(0xB1, 0x2000, 0, 0x7F, memory), 0);
+        assert_eq!(page_penalty(0xB1, 0x2000, 0, 0x80, memory), 1);
+    }
+}
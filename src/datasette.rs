@@ -0,0 +1,255 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TAP_SIGNATURE: &[u8; 12] = b"C64-TAPE-RAW";
+const TAP_HEADER_SIZE: usize = 20;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TapeButton {
+    Stop,
+    Play,
+    Record,
+}
+
+pub struct Tape {
+    path: PathBuf,
+    pulses: Vec<u32>,
+    modified: bool,
+}
+
+impl Tape {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = fs::read(path)
+            .map_err(|e| format!("Failed to open tape '{}': {}", path.display(), e))?;
+        let pulses = parse_tap(&data)
+            .ok_or_else(|| format!("'{}' is not a valid TAP file", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            pulses,
+            modified: false,
+        })
+    }
+
+    pub fn blank(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            pulses: Vec::new(),
+            modified: true,
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.modified {
+            fs::write(&self.path, encode_tap(&self.pulses))
+                .map_err(|e| format!("Failed to write tape '{}': {}", self.path.display(), e))?;
+            self.modified = false;
+        }
+        Ok(())
+    }
+}
+
+pub struct Datasette {
+    tape: Option<Tape>,
+    button: TapeButton,
+    position: usize,
+    pulse_length: u32,
+    pulse_remaining: u32,
+    read_level: bool,
+    write_level: bool,
+    write_cycles: u32,
+}
+
+impl Datasette {
+    pub fn new() -> Self {
+        Self {
+            tape: None,
+            button: TapeButton::Stop,
+            position: 0,
+            pulse_length: 0,
+            pulse_remaining: 0,
+            read_level: true,
+            write_level: true,
+            write_cycles: 0,
+        }
+    }
+
+    pub fn insert(&mut self, tape: Tape) -> Result<(), Box<dyn std::error::Error>> {
+        self.eject()?;
+        self.tape = Some(tape);
+        Ok(())
+    }
+
+    pub fn eject(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop()?;
+        self.tape = None;
+        self.position = 0;
+        Ok(())
+    }
+
+    pub fn play(&mut self) {
+        if self.tape.is_some() {
+            self.button = TapeButton::Play;
+        }
+    }
+
+    pub fn record(&mut self) {
+        if self.tape.is_some() {
+            self.button = TapeButton::Record;
+            self.write_cycles = 0;
+        }
+    }
+
+    pub fn stop(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.button = TapeButton::Stop;
+        self.pulse_remaining = 0;
+        self.read_level = true;
+        match self.tape.as_mut() {
+            Some(tape) => tape.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn rewind(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop()?;
+        self.position = 0;
+        Ok(())
+    }
+
+    pub fn sense(&self) -> bool {
+        self.button != TapeButton::Stop
+    }
+
+    pub fn read_level(&self) -> bool {
+        self.read_level
+    }
+
+    pub fn tick(&mut self, motor: bool, write_level: bool) {
+        if !motor {
+            return;
+        }
+        match self.button {
+            TapeButton::Play => self.play_tick(),
+            TapeButton::Record => self.record_tick(write_level),
+            TapeButton::Stop => {}
+        }
+    }
+
+    fn play_tick(&mut self) {
+        if self.pulse_remaining == 0 {
+            let Some(&pulse) = self
+                .tape
+                .as_ref()
+                .and_then(|tape| tape.pulses.get(self.position))
+            else {
+                self.read_level = true;
+                return;
+            };
+            self.position += 1;
+            self.pulse_length = pulse.max(2);
+            self.pulse_remaining = self.pulse_length;
+            self.read_level = false;
+        }
+
+        self.pulse_remaining -= 1;
+        if self.pulse_remaining <= self.pulse_length / 2 {
+            self.read_level = true;
+        }
+    }
+
+    fn record_tick(&mut self, write_level: bool) {
+        self.write_cycles = self.write_cycles.saturating_add(1);
+        let rising = write_level && !self.write_level;
+        self.write_level = write_level;
+        if !rising {
+            return;
+        }
+
+        let Some(tape) = self.tape.as_mut() else {
+            return;
+        };
+        tape.pulses.truncate(self.position);
+        tape.pulses.push(self.write_cycles);
+        tape.modified = true;
+        self.position += 1;
+        self.write_cycles = 0;
+    }
+}
+
+fn parse_tap(data: &[u8]) -> Option<Vec<u32>> {
+    if data.len() < TAP_HEADER_SIZE || &data[..12] != TAP_SIGNATURE {
+        return None;
+    }
+    let version = data[12];
+    let length = u32::from_le_bytes(data[16..20].try_into().ok()?) as usize;
+    let end = (TAP_HEADER_SIZE + length).min(data.len());
+
+    let mut pulses = Vec::new();
+    let mut i = TAP_HEADER_SIZE;
+    while i < end {
+        let byte = data[i];
+        i += 1;
+        if byte != 0 {
+            pulses.push(byte as u32 * 8);
+        } else if version == 0 {
+            pulses.push(256 * 8);
+        } else {
+            let bytes = data.get(i..i + 3)?;
+            pulses.push(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]));
+            i += 3;
+        }
+    }
+    Some(pulses)
+}
+
+fn encode_tap(pulses: &[u32]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(pulses.len());
+    for &pulse in pulses {
+        let value = pulse.div_ceil(8);
+        if (1..=255).contains(&value) {
+            body.push(value as u8);
+        } else {
+            let cycles = pulse.min(0xFF_FFFF).to_le_bytes();
+            body.extend([0, cycles[0], cycles[1], cycles[2]]);
+        }
+    }
+
+    let mut data = TAP_SIGNATURE.to_vec();
+    data.extend([1, 0, 0, 0]);
+    data.extend((body.len() as u32).to_le_bytes());
+    data.extend(body);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tap_round_trip() {
+        let pulses = vec![0x30 * 8, 0x42 * 8, 5000, 0x2B * 8];
+        let data = encode_tap(&pulses);
+        assert_eq!(&data[..12], TAP_SIGNATURE);
+        assert_eq!(data.len(), TAP_HEADER_SIZE + 7);
+        assert_eq!(parse_tap(&data), Some(pulses));
+    }
+
+    #[test]
+    fn test_playback_edges() {
+        let mut datasette = Datasette::new();
+        let mut tape = Tape::blank(Path::new("unused.tap"));
+        tape.pulses = vec![8, 8];
+        datasette.insert(tape).unwrap();
+        datasette.play();
+        assert!(datasette.sense());
+
+        let mut levels = Vec::new();
+        for _ in 0..16 {
+            datasette.tick(true, true);
+            levels.push(datasette.read_level());
+        }
+        let falling = levels.windows(2).filter(|w| w[0] && !w[1]).count();
+        assert_eq!(falling, 1);
+        assert!(!levels[0]);
+        assert!(levels[15]);
+    }
+}
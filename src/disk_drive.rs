@@ -0,0 +1,487 @@
+use crate::cbm_dos::{
+    matches_pattern, parse_file_spec, AccessMode, DirectoryListing, DosDevice, DosError,
+    DosStorage, FileType,
+};
+use crate::disk_image::{DiskFormat, DiskImage};
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DriveModel {
+    Cbm2031,
+    Cbm8250,
+}
+
+impl DriveModel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "2031" => Some(DriveModel::Cbm2031),
+            "8250" => Some(DriveModel::Cbm8250),
+            _ => None,
+        }
+    }
+
+    fn drives(self) -> usize {
+        match self {
+            DriveModel::Cbm2031 => 1,
+            DriveModel::Cbm8250 => 2,
+        }
+    }
+
+    fn dos_version(self) -> &'static str {
+        match self {
+            DriveModel::Cbm2031 => "CBM DOS V2.6 2031",
+            DriveModel::Cbm8250 => "CBM DOS V2.7 8250",
+        }
+    }
+
+    pub fn accepts(self, format: DiskFormat) -> bool {
+        matches!(
+            (self, format),
+            (DriveModel::Cbm2031, DiskFormat::D64)
+                | (DriveModel::Cbm8250, DiskFormat::D80 | DiskFormat::D82)
+        )
+    }
+}
+
+pub struct PendingFile {
+    drive: u8,
+    name: Vec<u8>,
+    file_type: FileType,
+    replace: bool,
+}
+
+pub struct DiskStorage {
+    model: DriveModel,
+    images: Vec<Option<DiskImage>>,
+}
+
+pub type DiskDrive = DosDevice<DiskStorage>;
+
+impl DiskDrive {
+    pub fn new(device_number: u8, model: DriveModel) -> Self {
+        let storage = DiskStorage {
+            model,
+            images: (0..model.drives()).map(|_| None).collect(),
+        };
+        DosDevice::with_storage(device_number, model.dos_version(), storage)
+    }
+}
+
+impl DiskStorage {
+    fn insert(&mut self, drive: u8, image: DiskImage) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.model.accepts(image.format()) {
+            return Err(format!(
+                "{:?} images cannot be used in a {:?}",
+                image.format(),
+                self.model
+            )
+            .into());
+        }
+        let slot = self
+            .images
+            .get_mut(drive as usize)
+            .ok_or_else(|| format!("{:?} has no drive {}", self.model, drive))?;
+        *slot = Some(image);
+        Ok(())
+    }
+
+    fn image(&self, drive: u8) -> Result<&DiskImage, DosError> {
+        self.images
+            .get(drive as usize)
+            .and_then(|image| image.as_ref())
+            .ok_or(DosError::DriveNotReady)
+    }
+
+    fn image_mut(&mut self, drive: u8) -> Result<&mut DiskImage, DosError> {
+        self.images
+            .get_mut(drive as usize)
+            .and_then(|image| image.as_mut())
+            .ok_or(DosError::DriveNotReady)
+    }
+
+    fn store(
+        &mut self,
+        drive: u8,
+        name: &[u8],
+        file_type: FileType,
+        replace: bool,
+        data: &[u8],
+    ) -> Result<(), DosError> {
+        let image = self.image_mut(drive)?;
+        if replace {
+            image.replace_file(name, file_type, data)?;
+        } else {
+            image.write_file(name, file_type, data)?;
+        }
+        image.flush()
+    }
+
+    fn copy_all(&mut self, from: u8, to: u8) -> Result<(), DosError> {
+        if from == to {
+            return Err(DosError::SyntaxError);
+        }
+        let source = self.image(from)?;
+        let files: Vec<_> = source
+            .files()
+            .into_iter()
+            .filter(|file| file.entry.closed)
+            .map(|file| (source.read_file(&file), file.entry))
+            .collect();
+        self.image(to)?;
+        for (data, entry) in files {
+            self.store(to, &entry.name, entry.file_type, false, &data)?;
+        }
+        Ok(())
+    }
+
+    fn copy_files(&mut self, drive: u8, new: &[u8], sources: &[u8]) -> Result<(), DosError> {
+        let mut data = Vec::new();
+        let mut file_type = None;
+        for source in sources.split(|&b| b == b',') {
+            let spec = parse_file_spec(source);
+            let image = self.image(spec.drive.unwrap_or(drive))?;
+            let file = image
+                .find_file(&spec.name, None)
+                .ok_or(DosError::FileNotFound)?;
+            file_type.get_or_insert(file.entry.file_type);
+            data.extend(image.read_file(&file));
+        }
+        let file_type = file_type.unwrap_or(FileType::Seq);
+        self.store(drive, new, file_type, false, &data)
+    }
+}
+
+impl DosStorage for DiskStorage {
+    type Target = PendingFile;
+
+    fn directory(&self, argument: &[u8]) -> Result<Vec<u8>, DosError> {
+        let (drives, pattern) = match argument.iter().position(|&b| b == b':') {
+            Some(colon) => (&argument[..colon], &argument[colon + 1..]),
+            None => (argument, b"*".as_slice()),
+        };
+        let drives: Vec<u8> = match drives.iter().find(|b| b.is_ascii_digit()) {
+            Some(digit) => vec![digit - b'0'],
+            None => (0..self.images.len() as u8)
+                .filter(|&drive| self.image(drive).is_ok())
+                .collect(),
+        };
+        if drives.is_empty() {
+            return Err(DosError::DriveNotReady);
+        }
+
+        let mut listing = DirectoryListing::new();
+        for drive in drives {
+            let image = self.image(drive)?;
+            listing.add_header(drive, &image.disk_name(), &image.disk_id());
+            for file in image.files() {
+                if matches_pattern(pattern, &file.entry.name) {
+                    listing.add_entry(&file.entry);
+                }
+            }
+            listing.add_blocks_free(image.blocks_free());
+        }
+        Ok(listing.finish())
+    }
+
+    fn read_file(
+        &self,
+        drive: u8,
+        name: &[u8],
+        file_type: Option<FileType>,
+    ) -> Result<Vec<u8>, DosError> {
+        let image = self.image(drive)?;
+        let file = image
+            .find_file(name, file_type)
+            .ok_or(DosError::FileNotFound)?;
+        Ok(image.read_file(&file))
+    }
+
+    fn create_file(
+        &mut self,
+        drive: u8,
+        name: &[u8],
+        file_type: FileType,
+        mode: AccessMode,
+        replace: bool,
+    ) -> Result<(PendingFile, Vec<u8>), DosError> {
+        let image = self.image(drive)?;
+        let existing = image.find_exact(name);
+        let data = match (mode, existing) {
+            (AccessMode::Append, Some(file)) => image.read_file(&file),
+            (AccessMode::Append, None) => return Err(DosError::FileNotFound),
+            (_, Some(_)) if !replace => return Err(DosError::FileExists),
+            _ => Vec::new(),
+        };
+        if image.is_read_only() {
+            return Err(DosError::WriteProtectOn);
+        }
+        let file = PendingFile {
+            drive,
+            name: name.to_vec(),
+            file_type,
+            replace: replace || mode == AccessMode::Append,
+        };
+        Ok((file, data))
+    }
+
+    fn write_file(&mut self, file: PendingFile, data: &[u8]) -> Result<(), DosError> {
+        self.store(file.drive, &file.name, file.file_type, file.replace, data)
+    }
+
+    fn scratch(&mut self, drive: u8, pattern: &[u8]) -> Result<u8, DosError> {
+        let image = self.image_mut(drive)?;
+        let count = image.scratch(pattern)?;
+        image.flush()?;
+        Ok(count)
+    }
+
+    fn rename(&mut self, drive: u8, old: &[u8], new: &[u8]) -> Result<(), DosError> {
+        let image = self.image_mut(drive)?;
+        image.rename(old, new)?;
+        image.flush()
+    }
+
+    fn command(&mut self, drive: u8, command: &[u8], argument: &[u8]) -> Result<(), DosError> {
+        match command[0] {
+            b'I' => {
+                self.image(drive)?;
+                Ok(())
+            }
+            b'V' => {
+                let image = self.image_mut(drive)?;
+                image.validate()?;
+                image.flush()
+            }
+            b'N' => {
+                let image = self.image_mut(drive)?;
+                if image.is_read_only() {
+                    return Err(DosError::WriteProtectOn);
+                }
+                let mut parts = argument.splitn(2, |&b| b == b',');
+                let name = parts.next().unwrap_or_default();
+                let id = parts.next();
+                image.format_disk(name, id);
+                image.flush()
+            }
+            b'C' => {
+                if argument.is_empty() {
+                    let from = command
+                        .iter()
+                        .skip_while(|&&b| b != b'=')
+                        .find(|b| b.is_ascii_digit())
+                        .ok_or(DosError::SyntaxError)?
+                        - b'0';
+                    self.copy_all(from, drive)
+                } else {
+                    let equals = argument
+                        .iter()
+                        .position(|&b| b == b'=')
+                        .ok_or(DosError::SyntaxError)?;
+                    self.copy_files(drive, &argument[..equals], &argument[equals + 1..])
+                }
+            }
+            _ => Err(DosError::InvalidCommand),
+        }
+    }
+
+    fn mount(&mut self, drive: u8, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let image = DiskImage::open(path)?;
+        self.insert(drive, image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ieee488::tests::{receive, send};
+    use crate::ieee488::{Ieee488Bus, Ieee488Device};
+
+    fn dual_drive() -> DiskDrive {
+        let mut drive = DiskDrive::new(9, DriveModel::Cbm8250);
+        for unit in 0..2 {
+            let image = DiskImage::blank(DiskFormat::D80, b"TEST", b"01");
+            drive.storage.insert(unit, image).unwrap();
+        }
+        drive
+    }
+
+    fn status(drive: &mut DiskDrive) -> String {
+        let mut message = Vec::new();
+        loop {
+            let (byte, eoi) = drive.read_byte(15).unwrap();
+            message.push(byte);
+            if eoi {
+                break;
+            }
+        }
+        String::from_utf8(message).unwrap().trim_end().to_string()
+    }
+
+    fn command(drive: &mut DiskDrive, text: &str) -> String {
+        drive.open(15, text.as_bytes());
+        status(drive)
+    }
+
+    fn write(drive: &mut DiskDrive, name: &str, data: &[u8]) -> String {
+        drive.open(1, name.as_bytes());
+        let result = status(drive);
+        for &byte in data {
+            drive.write_byte(1, byte);
+        }
+        drive.close(1);
+        result
+    }
+
+    fn read(drive: &mut DiskDrive, name: &str) -> Vec<u8> {
+        drive.open(0, name.as_bytes());
+        let mut data = Vec::new();
+        while let Some((byte, eoi)) = drive.read_byte(0) {
+            data.push(byte);
+            if eoi {
+                break;
+            }
+        }
+        drive.close(0);
+        data
+    }
+
+    #[test]
+    fn test_command_channel() {
+        let mut drive = dual_drive();
+        assert_eq!(status(&mut drive), "73,CBM DOS V2.7 8250,00,00");
+
+        assert_eq!(write(&mut drive, "0:FILE", &[1, 4, 1]), "00, OK,00,00");
+        assert_eq!(
+            write(&mut drive, "0:FILE", &[1, 4, 2]),
+            "63,FILE EXISTS,00,00"
+        );
+        assert_eq!(write(&mut drive, "@0:FILE", &[1, 4, 3]), "00, OK,00,00");
+        assert_eq!(read(&mut drive, "0:FILE"), [1, 4, 3]);
+        assert_eq!(drive.storage.image(0).unwrap().files().len(), 1);
+
+        assert_eq!(command(&mut drive, "R0:NEW=FILE"), "00, OK,00,00");
+        assert!(read(&mut drive, "0:FILE").is_empty());
+        assert_eq!(status(&mut drive), "62,FILE NOT FOUND,00,00");
+        assert_eq!(command(&mut drive, "R0:NEW=FILE"), "63,FILE EXISTS,00,00");
+        assert_eq!(
+            command(&mut drive, "R0:OTHER=FILE"),
+            "62,FILE NOT FOUND,00,00"
+        );
+
+        assert_eq!(command(&mut drive, "C1=0"), "00, OK,00,00");
+        assert_eq!(read(&mut drive, "1:NEW"), [1, 4, 3]);
+        assert_eq!(command(&mut drive, "C1=1"), "30,SYNTAX ERROR,00,00");
+
+        assert_eq!(command(&mut drive, "S0:NEW"), "01,FILES SCRATCHED,01,00");
+        assert_eq!(command(&mut drive, "S0:NEW"), "01,FILES SCRATCHED,00,00");
+
+        assert_eq!(command(&mut drive, "N1:WORK,W1"), "00, OK,00,00");
+        let image = drive.storage.image(1).unwrap();
+        assert!(image.files().is_empty());
+        assert!(image.disk_name().starts_with(b"WORK\xA0"));
+        assert_eq!(&image.disk_id()[..2], b"W1");
+
+        assert_eq!(command(&mut drive, "X"), "31,SYNTAX ERROR,00,00");
+        assert_eq!(write(&mut drive, "2:FILE", &[]), "74,DRIVE NOT READY,00,00");
+    }
+
+    #[test]
+    fn test_command_channel_open_and_close() {
+        let mut drive = dual_drive();
+        drive.open(15, b"");
+        drive.open(2, b"0:NOTES,S,W");
+        for &byte in b"HELLO" {
+            drive.write_byte(2, byte);
+        }
+        for &byte in b"R0:MEMO=NOTES" {
+            drive.write_byte(15, byte);
+        }
+        drive.close(15);
+        assert_eq!(read(&mut drive, "0:NOTES,S"), b"HELLO");
+
+        drive.open(15, b"");
+        for &byte in b"R0:MEMO=NOTES\r" {
+            drive.write_byte(15, byte);
+        }
+        drive.unlisten(15);
+        assert_eq!(status(&mut drive), "00, OK,00,00");
+        assert_eq!(read(&mut drive, "0:MEMO,S"), b"HELLO");
+    }
+
+    fn open_file(bus: &mut Ieee488Bus, secondary: u8, name: &[u8]) {
+        send(bus, 0x28, true);
+        send(bus, 0xF0 | secondary, true);
+        for &byte in name {
+            send(bus, byte, false);
+        }
+        send(bus, 0x3F, true);
+    }
+
+    fn close_file(bus: &mut Ieee488Bus, secondary: u8) {
+        send(bus, 0x28, true);
+        send(bus, 0xE0 | secondary, true);
+        send(bus, 0x3F, true);
+    }
+
+    fn bus_load(bus: &mut Ieee488Bus, name: &[u8]) -> Vec<u8> {
+        open_file(bus, 0, name);
+        send(bus, 0x48, true);
+        send(bus, 0x60, true);
+        let mut data = Vec::new();
+        loop {
+            let (byte, eoi) = receive(bus);
+            data.push(byte);
+            if eoi {
+                break;
+            }
+        }
+        send(bus, 0x5F, true);
+        close_file(bus, 0);
+        data
+    }
+
+    fn bus_save(bus: &mut Ieee488Bus, name: &[u8], data: &[u8]) {
+        open_file(bus, 1, name);
+        send(bus, 0x28, true);
+        send(bus, 0x61, true);
+        for &byte in data {
+            send(bus, byte, false);
+        }
+        send(bus, 0x3F, true);
+        close_file(bus, 1);
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    #[test]
+    fn test_load_and_save_over_ieee488() {
+        let mut drive = DiskDrive::new(8, DriveModel::Cbm2031);
+        let image = DiskImage::blank(DiskFormat::D64, b"GAMES", b"01");
+        drive.storage.insert(0, image).unwrap();
+        let mut bus = Ieee488Bus::new();
+        bus.attach(Box::new(drive));
+
+        let program: Vec<u8> = [0x01, 0x04]
+            .into_iter()
+            .chain((0..300).map(|i| i as u8))
+            .collect();
+        bus_save(&mut bus, b"PROG", &program);
+        assert_eq!(bus_load(&mut bus, b"PROG"), program);
+        assert_eq!(bus_load(&mut bus, b"P*"), program);
+
+        let listing = bus_load(&mut bus, b"$");
+        assert_eq!(listing[..2], [0x01, 0x04]);
+        assert!(contains(&listing, b"\"GAMES"));
+        assert!(contains(&listing, b"\x02\x00   \"PROG\"             PRG "));
+        assert!(contains(&listing, b"\x96\x02BLOCKS FREE."));
+
+        let device = bus.device_mut(8).unwrap();
+        device.open(0, b"MISSING");
+        assert_eq!(device.read_byte(0), None);
+        assert_eq!(device.read_byte(15), Some((b'6', false)));
+    }
+}
@@ -0,0 +1,794 @@
+use crate::cbm_dos::{matches_pattern, DirectoryEntry, DosError, FileType};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SECTOR_SIZE: usize = 256;
+const DIR_ENTRY_SIZE: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiskFormat {
+    D64,
+    D80,
+    D82,
+}
+
+impl DiskFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let lower = path.to_lowercase();
+        if lower.ends_with(".d64") {
+            Some(DiskFormat::D64)
+        } else if lower.ends_with(".d80") {
+            Some(DiskFormat::D80)
+        } else if lower.ends_with(".d82") {
+            Some(DiskFormat::D82)
+        } else {
+            None
+        }
+    }
+
+    fn from_size(size: usize) -> Option<Self> {
+        [DiskFormat::D64, DiskFormat::D80, DiskFormat::D82]
+            .into_iter()
+            .find(|format| {
+                let sectors = format.total_sectors();
+                size == sectors * SECTOR_SIZE || size == sectors * (SECTOR_SIZE + 1)
+            })
+    }
+
+    fn total_sectors(self) -> usize {
+        (1..=self.tracks())
+            .map(|t| self.sectors_per_track(t) as usize)
+            .sum()
+    }
+
+    pub fn tracks(self) -> u8 {
+        match self {
+            DiskFormat::D64 => 35,
+            DiskFormat::D80 => 77,
+            DiskFormat::D82 => 154,
+        }
+    }
+
+    pub fn sectors_per_track(self, track: u8) -> u8 {
+        match self {
+            DiskFormat::D64 => match track {
+                1..=17 => 21,
+                18..=24 => 19,
+                25..=30 => 18,
+                _ => 17,
+            },
+            DiskFormat::D80 | DiskFormat::D82 => match (track - 1) % 77 + 1 {
+                1..=39 => 29,
+                40..=53 => 27,
+                54..=64 => 25,
+                _ => 23,
+            },
+        }
+    }
+
+    fn header_sector(self) -> (u8, u8) {
+        match self {
+            DiskFormat::D64 => (18, 0),
+            DiskFormat::D80 | DiskFormat::D82 => (39, 0),
+        }
+    }
+
+    fn first_dir_sector(self) -> (u8, u8) {
+        match self {
+            DiskFormat::D64 => (18, 1),
+            DiskFormat::D80 | DiskFormat::D82 => (39, 1),
+        }
+    }
+
+    fn bam_sectors(self) -> &'static [(u8, u8)] {
+        match self {
+            DiskFormat::D64 => &[(18, 0)],
+            DiskFormat::D80 => &[(38, 0), (38, 3)],
+            DiskFormat::D82 => &[(38, 0), (38, 3), (38, 6), (38, 9)],
+        }
+    }
+
+    fn tracks_per_bam_sector(self) -> u8 {
+        match self {
+            DiskFormat::D64 => 35,
+            DiskFormat::D80 | DiskFormat::D82 => 50,
+        }
+    }
+
+    fn bam_bitmap_bytes(self) -> u8 {
+        match self {
+            DiskFormat::D64 => 3,
+            DiskFormat::D80 | DiskFormat::D82 => 4,
+        }
+    }
+
+    fn name_offset(self) -> usize {
+        match self {
+            DiskFormat::D64 => 0x90,
+            DiskFormat::D80 | DiskFormat::D82 => 0x06,
+        }
+    }
+
+    fn id_offset(self) -> usize {
+        match self {
+            DiskFormat::D64 => 0xA2,
+            DiskFormat::D80 | DiskFormat::D82 => 0x18,
+        }
+    }
+
+    fn dos_type(self) -> &'static [u8; 2] {
+        match self {
+            DiskFormat::D64 => b"2A",
+            DiskFormat::D80 | DiskFormat::D82 => b"2C",
+        }
+    }
+
+    fn is_system_track(self, track: u8) -> bool {
+        track == self.header_sector().0
+    }
+
+    fn file_interleave(self) -> u8 {
+        match self {
+            DiskFormat::D64 => 10,
+            DiskFormat::D80 | DiskFormat::D82 => 1,
+        }
+    }
+
+    fn dir_interleave(self) -> u8 {
+        3
+    }
+}
+
+pub struct ImageFile {
+    pub entry: DirectoryEntry,
+    pub track: u8,
+    pub sector: u8,
+    slot: usize,
+}
+
+pub struct DiskImage {
+    format: DiskFormat,
+    data: Vec<u8>,
+    path: Option<PathBuf>,
+    read_only: bool,
+}
+
+impl DiskImage {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = fs::read(path)
+            .map_err(|e| format!("Failed to open disk image '{}': {}", path.display(), e))?;
+        let format = DiskFormat::from_size(data.len()).ok_or_else(|| {
+            format!(
+                "Unrecognised disk image '{}' ({} bytes)",
+                path.display(),
+                data.len()
+            )
+        })?;
+        let read_only = fs::metadata(path)
+            .map(|m| m.permissions().readonly())
+            .unwrap_or(false);
+        Ok(Self {
+            format,
+            data,
+            path: Some(path.to_path_buf()),
+            read_only,
+        })
+    }
+
+    pub fn format(&self) -> DiskFormat {
+        self.format
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn sector_offset(&self, track: u8, sector: u8) -> Option<usize> {
+        if track == 0
+            || track > self.format.tracks()
+            || sector >= self.format.sectors_per_track(track)
+        {
+            return None;
+        }
+        let preceding: usize = (1..track)
+            .map(|t| self.format.sectors_per_track(t) as usize)
+            .sum();
+        Some((preceding + sector as usize) * SECTOR_SIZE)
+    }
+
+    pub fn read_sector(&self, track: u8, sector: u8) -> Option<&[u8]> {
+        let offset = self.sector_offset(track, sector)?;
+        Some(&self.data[offset..offset + SECTOR_SIZE])
+    }
+
+    fn sector_mut(&mut self, track: u8, sector: u8) -> Option<&mut [u8]> {
+        let offset = self.sector_offset(track, sector)?;
+        Some(&mut self.data[offset..offset + SECTOR_SIZE])
+    }
+
+    fn header_offset(&self) -> usize {
+        let (track, sector) = self.format.header_sector();
+        self.sector_offset(track, sector).unwrap()
+    }
+
+    pub fn disk_name(&self) -> Vec<u8> {
+        let offset = self.header_offset() + self.format.name_offset();
+        self.data[offset..offset + 16].to_vec()
+    }
+
+    pub fn disk_id(&self) -> Vec<u8> {
+        let offset = self.header_offset() + self.format.id_offset();
+        self.data[offset..offset + 5].to_vec()
+    }
+
+    fn bam_entry(&self, track: u8) -> usize {
+        let per_sector = self.format.tracks_per_bam_sector();
+        let (bam_track, bam_sector) =
+            self.format.bam_sectors()[((track - 1) / per_sector) as usize];
+        let offset = self.sector_offset(bam_track, bam_sector).unwrap();
+        let index = ((track - 1) % per_sector) as usize;
+        match self.format {
+            DiskFormat::D64 => offset + 4 + index * 4,
+            DiskFormat::D80 | DiskFormat::D82 => offset + 6 + index * 5,
+        }
+    }
+
+    fn is_free(&self, track: u8, sector: u8) -> bool {
+        let entry = self.bam_entry(track);
+        let byte = self.data[entry + 1 + sector as usize / 8];
+        byte & (1 << (sector % 8)) != 0
+    }
+
+    fn set_free(&mut self, track: u8, sector: u8, free: bool) {
+        if self.sector_offset(track, sector).is_none() || self.is_free(track, sector) == free {
+            return;
+        }
+        let entry = self.bam_entry(track);
+        let bit = 1 << (sector % 8);
+        let index = entry + 1 + sector as usize / 8;
+        if free {
+            self.data[index] |= bit;
+            self.data[entry] = self.data[entry].wrapping_add(1);
+        } else {
+            self.data[index] &= !bit;
+            self.data[entry] = self.data[entry].wrapping_sub(1);
+        }
+    }
+
+    fn free_on_track(&self, track: u8) -> u16 {
+        self.data[self.bam_entry(track)] as u16
+    }
+
+    pub fn blocks_free(&self) -> u16 {
+        (1..=self.format.tracks())
+            .filter(|&t| !self.format.is_system_track(t))
+            .map(|t| self.free_on_track(t))
+            .sum()
+    }
+
+    fn allocate_on_track(&mut self, track: u8, start: u8) -> Option<u8> {
+        let count = self.format.sectors_per_track(track);
+        for i in 0..count {
+            let sector = (start + i) % count;
+            if self.is_free(track, sector) {
+                self.set_free(track, sector, false);
+                return Some(sector);
+            }
+        }
+        None
+    }
+
+    fn allocate(&mut self, previous: Option<(u8, u8)>) -> Option<(u8, u8)> {
+        if let Some((track, sector)) = previous {
+            let start =
+                (sector + self.format.file_interleave()) % self.format.sectors_per_track(track);
+            if let Some(sector) = self.allocate_on_track(track, start) {
+                return Some((track, sector));
+            }
+        }
+
+        let (dir_track, _) = self.format.header_sector();
+        for distance in 1..self.format.tracks() {
+            for track in [
+                dir_track.checked_sub(distance),
+                dir_track.checked_add(distance),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                if track == 0 || track > self.format.tracks() || self.format.is_system_track(track)
+                {
+                    continue;
+                }
+                if let Some(sector) = self.allocate_on_track(track, 0) {
+                    return Some((track, sector));
+                }
+            }
+        }
+        None
+    }
+
+    fn chain(&self, track: u8, sector: u8) -> Vec<(u8, u8)> {
+        let mut sectors = Vec::new();
+        let mut next = (track, sector);
+        let limit = self.data.len() / SECTOR_SIZE;
+        while next.0 != 0 && sectors.len() < limit {
+            let Some(data) = self.read_sector(next.0, next.1) else {
+                break;
+            };
+            sectors.push(next);
+            next = (data[0], data[1]);
+        }
+        sectors
+    }
+
+    fn directory_slots(&self) -> Vec<usize> {
+        let (track, sector) = self.format.first_dir_sector();
+        self.chain(track, sector)
+            .into_iter()
+            .filter_map(|(t, s)| self.sector_offset(t, s))
+            .flat_map(|offset| (0..8).map(move |i| offset + i * DIR_ENTRY_SIZE))
+            .collect()
+    }
+
+    pub fn files(&self) -> Vec<ImageFile> {
+        self.directory_slots()
+            .into_iter()
+            .filter_map(|slot| {
+                let raw = &self.data[slot..slot + DIR_ENTRY_SIZE];
+                let type_byte = raw[2];
+                if type_byte == 0 {
+                    return None;
+                }
+                let file_type = FileType::from_code(type_byte)?;
+                let name: Vec<u8> = raw[5..21]
+                    .iter()
+                    .copied()
+                    .take_while(|&b| b != 0xA0)
+                    .collect();
+                Some(ImageFile {
+                    entry: DirectoryEntry {
+                        name,
+                        file_type,
+                        blocks: u16::from_le_bytes([raw[30], raw[31]]),
+                        closed: type_byte & 0x80 != 0,
+                        locked: type_byte & 0x40 != 0,
+                    },
+                    track: raw[3],
+                    sector: raw[4],
+                    slot,
+                })
+            })
+            .collect()
+    }
+
+    pub fn find_file(&self, pattern: &[u8], file_type: Option<FileType>) -> Option<ImageFile> {
+        self.files().into_iter().find(|f| {
+            f.entry.closed
+                && matches_pattern(pattern, &f.entry.name)
+                && file_type.is_none_or(|t| t == f.entry.file_type)
+        })
+    }
+
+    pub fn find_exact(&self, name: &[u8]) -> Option<ImageFile> {
+        let name = &name[..name.len().min(16)];
+        self.files().into_iter().find(|f| f.entry.name == name)
+    }
+
+    pub fn read_file(&self, file: &ImageFile) -> Vec<u8> {
+        let mut result = Vec::new();
+        for (track, sector) in self.chain(file.track, file.sector) {
+            let data = self.read_sector(track, sector).unwrap();
+            if data[0] == 0 {
+                let last = (data[1] as usize).clamp(1, SECTOR_SIZE - 1);
+                result.extend(&data[2..=last]);
+            } else {
+                result.extend(&data[2..]);
+            }
+        }
+        result
+    }
+
+    fn free_dir_slot(&mut self) -> Option<usize> {
+        let slots = self.directory_slots();
+        if let Some(&slot) = slots.iter().find(|&&slot| self.data[slot + 2] == 0) {
+            return Some(slot);
+        }
+
+        let (dir_track, _) = self.format.first_dir_sector();
+        let last_slot = *slots.last()?;
+        let last_sector_offset = last_slot - 7 * DIR_ENTRY_SIZE;
+        let last_sector =
+            ((last_sector_offset - self.sector_offset(dir_track, 0)?) / SECTOR_SIZE) as u8;
+        let count = self.format.sectors_per_track(dir_track);
+        let start = (last_sector + self.format.dir_interleave()) % count;
+        let sector = self.allocate_on_track(dir_track, start)?;
+
+        self.data[last_sector_offset] = dir_track;
+        self.data[last_sector_offset + 1] = sector;
+        let new_sector = self.sector_mut(dir_track, sector)?;
+        new_sector.fill(0);
+        new_sector[1] = 0xFF;
+        self.sector_offset(dir_track, sector)
+    }
+
+    pub fn write_file(
+        &mut self,
+        name: &[u8],
+        file_type: FileType,
+        contents: &[u8],
+    ) -> Result<(), DosError> {
+        if self.read_only {
+            return Err(DosError::WriteProtectOn);
+        }
+        check_name(name)?;
+        if self.find_exact(name).is_some() {
+            return Err(DosError::FileExists);
+        }
+
+        let blocks = contents.len().div_ceil(SECTOR_SIZE - 2).max(1);
+        if blocks > self.blocks_free() as usize {
+            return Err(DosError::DiskFull);
+        }
+        let start = self.write_chain(contents, blocks)?;
+        let Some(slot) = self.free_dir_slot() else {
+            self.free_chain(start);
+            return Err(DosError::DiskFull);
+        };
+        self.write_entry(slot, name, file_type, start, blocks as u16);
+        Ok(())
+    }
+
+    pub fn replace_file(
+        &mut self,
+        name: &[u8],
+        file_type: FileType,
+        contents: &[u8],
+    ) -> Result<(), DosError> {
+        if self.read_only {
+            return Err(DosError::WriteProtectOn);
+        }
+        let Some(old) = self.find_exact(name) else {
+            return self.write_file(name, file_type, contents);
+        };
+        if old.entry.locked {
+            return Err(DosError::FileExists);
+        }
+
+        let blocks = contents.len().div_ceil(SECTOR_SIZE - 2).max(1);
+        if blocks > self.blocks_free() as usize {
+            return Err(DosError::DiskFull);
+        }
+        let start = self.write_chain(contents, blocks)?;
+        self.free_chain((old.track, old.sector));
+        self.write_entry(old.slot, name, file_type, start, blocks as u16);
+        Ok(())
+    }
+
+    fn write_chain(&mut self, contents: &[u8], blocks: usize) -> Result<(u8, u8), DosError> {
+        let mut sectors = Vec::with_capacity(blocks);
+        let mut previous = None;
+        for _ in 0..blocks {
+            let Some(next) = self.allocate(previous) else {
+                for (track, sector) in sectors {
+                    self.set_free(track, sector, true);
+                }
+                return Err(DosError::DiskFull);
+            };
+            sectors.push(next);
+            previous = Some(next);
+        }
+
+        let chunks: Vec<&[u8]> = if contents.is_empty() {
+            vec![&[]]
+        } else {
+            contents.chunks(SECTOR_SIZE - 2).collect()
+        };
+        for (i, chunk) in chunks.iter().enumerate() {
+            let (track, sector) = sectors[i];
+            let link = match sectors.get(i + 1) {
+                Some(&next) => next,
+                None => (0, (chunk.len() + 1) as u8),
+            };
+            let data = self.sector_mut(track, sector).ok_or(DosError::WriteError)?;
+            data.fill(0);
+            data[0] = link.0;
+            data[1] = link.1;
+            data[2..2 + chunk.len()].copy_from_slice(chunk);
+        }
+        Ok(sectors[0])
+    }
+
+    fn free_chain(&mut self, start: (u8, u8)) {
+        for (track, sector) in self.chain(start.0, start.1) {
+            self.set_free(track, sector, true);
+        }
+    }
+
+    fn write_entry(
+        &mut self,
+        slot: usize,
+        name: &[u8],
+        file_type: FileType,
+        start: (u8, u8),
+        blocks: u16,
+    ) {
+        let entry = &mut self.data[slot + 2..slot + DIR_ENTRY_SIZE];
+        entry.fill(0);
+        entry[0] = 0x80 | file_type.code();
+        entry[1] = start.0;
+        entry[2] = start.1;
+        entry[3..19].fill(0xA0);
+        entry[3..3 + name.len().min(16)].copy_from_slice(&name[..name.len().min(16)]);
+        entry[28..30].copy_from_slice(&blocks.to_le_bytes());
+    }
+
+    pub fn scratch(&mut self, pattern: &[u8]) -> Result<u8, DosError> {
+        if self.read_only {
+            return Err(DosError::WriteProtectOn);
+        }
+        let mut count = 0u8;
+        for file in self.files() {
+            if file.entry.locked || !matches_pattern(pattern, &file.entry.name) {
+                continue;
+            }
+            self.free_chain((file.track, file.sector));
+            self.data[file.slot + 2] = 0;
+            count = count.saturating_add(1);
+        }
+        Ok(count)
+    }
+
+    pub fn rename(&mut self, old: &[u8], new: &[u8]) -> Result<(), DosError> {
+        if self.read_only {
+            return Err(DosError::WriteProtectOn);
+        }
+        check_name(new)?;
+        if self.find_exact(new).is_some() {
+            return Err(DosError::FileExists);
+        }
+        let file = self.find_file(old, None).ok_or(DosError::FileNotFound)?;
+        let name = &mut self.data[file.slot + 5..file.slot + 21];
+        name.fill(0xA0);
+        name[..new.len().min(16)].copy_from_slice(&new[..new.len().min(16)]);
+        Ok(())
+    }
+
+    pub fn format_disk(&mut self, name: &[u8], id: Option<&[u8]>) {
+        let (dir_track, dir_sector) = self.format.first_dir_sector();
+        let header = self.header_offset();
+        let old_id = self.disk_id();
+
+        if id.is_some() {
+            self.data.fill(0);
+        }
+
+        for track in 1..=self.format.tracks() {
+            let entry = self.bam_entry(track);
+            let count = self.format.sectors_per_track(track);
+            self.data[entry] = count;
+            for i in 0..self.format.bam_bitmap_bytes() {
+                let bits = count.saturating_sub(i * 8).min(8);
+                self.data[entry + 1 + i as usize] = ((1u16 << bits) - 1) as u8;
+            }
+        }
+
+        let bam_sectors = self.format.bam_sectors();
+        let per_sector = self.format.tracks_per_bam_sector();
+        for (i, &(track, sector)) in bam_sectors.iter().enumerate() {
+            if (track, sector) == self.format.header_sector() {
+                continue;
+            }
+            let link = bam_sectors
+                .get(i + 1)
+                .copied()
+                .unwrap_or((dir_track, dir_sector));
+            let low = i as u8 * per_sector + 1;
+            let high = (low + per_sector).min(self.format.tracks() + 1);
+            let format = self.format;
+            if let Some(bam) = self.sector_mut(track, sector) {
+                bam[..6].copy_from_slice(&[link.0, link.1, format.dos_type()[1], 0, low, high]);
+            }
+        }
+
+        let header_link = match bam_sectors[0] {
+            bam if bam == self.format.header_sector() => (dir_track, dir_sector),
+            bam => bam,
+        };
+        let header_data = &mut self.data[header..header + SECTOR_SIZE];
+        header_data[0] = header_link.0;
+        header_data[1] = header_link.1;
+        header_data[2] = self.format.dos_type()[1];
+        let name_offset = self.format.name_offset();
+        header_data[name_offset..name_offset + 27].fill(0xA0);
+        header_data[name_offset..name_offset + name.len().min(16)]
+            .copy_from_slice(&name[..name.len().min(16)]);
+        let id_offset = self.format.id_offset();
+        match id {
+            Some(id) => {
+                header_data[id_offset] = id.first().copied().unwrap_or(b' ');
+                header_data[id_offset + 1] = id.get(1).copied().unwrap_or(b' ');
+            }
+            None => header_data[id_offset..id_offset + 2].copy_from_slice(&old_id[..2]),
+        }
+        header_data[id_offset + 3..id_offset + 5].copy_from_slice(self.format.dos_type());
+
+        let (header_track, header_sector) = self.format.header_sector();
+        self.set_free(header_track, header_sector, false);
+        for &(track, sector) in bam_sectors {
+            self.set_free(track, sector, false);
+        }
+        self.set_free(dir_track, dir_sector, false);
+        if let Some(dir) = self.sector_mut(dir_track, dir_sector) {
+            dir.fill(0);
+            dir[1] = 0xFF;
+        }
+    }
+
+    pub fn validate(&mut self) -> Result<(), DosError> {
+        if self.read_only {
+            return Err(DosError::WriteProtectOn);
+        }
+        for track in 1..=self.format.tracks() {
+            for sector in 0..self.format.sectors_per_track(track) {
+                self.set_free(track, sector, true);
+            }
+        }
+        let (header_track, header_sector) = self.format.header_sector();
+        self.set_free(header_track, header_sector, false);
+        for &(track, sector) in self.format.bam_sectors() {
+            self.set_free(track, sector, false);
+        }
+        let (dir_track, dir_sector) = self.format.first_dir_sector();
+        for (track, sector) in self.chain(dir_track, dir_sector) {
+            self.set_free(track, sector, false);
+        }
+        for file in self.files() {
+            if !file.entry.closed {
+                self.data[file.slot + 2] = 0;
+                continue;
+            }
+            for (track, sector) in self.chain(file.track, file.sector) {
+                self.set_free(track, sector, false);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn blank(format: DiskFormat, name: &[u8], id: &[u8]) -> DiskImage {
+        let mut image = DiskImage {
+            format,
+            data: vec![0; format.total_sectors() * SECTOR_SIZE],
+            path: None,
+            read_only: false,
+        };
+        image.format_disk(name, Some(id));
+        image
+    }
+
+    pub fn flush(&self) -> Result<(), DosError> {
+        if let Some(path) = &self.path {
+            fs::write(path, &self.data).map_err(|_| DosError::WriteError)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_name(name: &[u8]) -> Result<(), DosError> {
+    if name.iter().any(|&b| b == b'*' || b == b'?') {
+        return Err(DosError::SyntaxError);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blank_d64() {
+        let image = DiskImage::blank(DiskFormat::D64, b"TEST", b"01");
+        assert_eq!(image.data.len(), 174848);
+        assert_eq!(image.blocks_free(), 664);
+        assert_eq!(&image.disk_id(), b"01\xA02A");
+        assert!(image.files().is_empty());
+    }
+
+    #[test]
+    fn test_blank_d80_and_d82() {
+        let image = DiskImage::blank(DiskFormat::D80, b"TEST", b"01");
+        assert_eq!(image.data.len(), 533248);
+        assert_eq!(image.blocks_free(), 2052);
+        assert_eq!(&image.disk_id(), b"01\xA02C");
+        assert_eq!(image.read_sector(38, 3).unwrap()[4..6], [51, 78]);
+
+        let image = DiskImage::blank(DiskFormat::D82, b"TEST", b"01");
+        assert_eq!(image.data.len(), 1066496);
+        assert_eq!(image.blocks_free(), 4133);
+        assert_eq!(
+            image.read_sector(38, 9).unwrap()[..6],
+            [39, 1, b'C', 0, 151, 155]
+        );
+    }
+
+    #[test]
+    fn test_write_and_read_file() {
+        let mut image = DiskImage::blank(DiskFormat::D64, b"TEST", b"01");
+        let contents: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        image.write_file(b"DATA", FileType::Prg, &contents).unwrap();
+
+        let file = image.find_file(b"DA*", Some(FileType::Prg)).unwrap();
+        assert_eq!(file.entry.blocks, 3);
+        assert_eq!(image.read_file(&file), contents);
+        assert_eq!(image.blocks_free(), 661);
+        assert_eq!(
+            image.write_file(b"DATA", FileType::Prg, &contents),
+            Err(DosError::FileExists)
+        );
+
+        assert_eq!(image.scratch(b"DATA"), Ok(1));
+        assert_eq!(image.blocks_free(), 664);
+        assert!(image.find_file(b"DATA", None).is_none());
+    }
+
+    #[test]
+    fn test_replace_file() {
+        let mut image = DiskImage::blank(DiskFormat::D64, b"TEST", b"01");
+        image.write_file(b"DATA", FileType::Prg, &[1; 300]).unwrap();
+        image
+            .replace_file(b"DATA", FileType::Prg, &[2; 10])
+            .unwrap();
+        assert_eq!(image.files().len(), 1);
+        let file = image.find_file(b"DATA", None).unwrap();
+        assert_eq!(image.read_file(&file), [2; 10]);
+        assert_eq!(image.blocks_free(), 663);
+
+        assert_eq!(
+            image.replace_file(b"DATA", FileType::Prg, &[3; 254 * 664]),
+            Err(DosError::DiskFull)
+        );
+        let file = image.find_file(b"DATA", None).unwrap();
+        assert_eq!(image.read_file(&file), [2; 10]);
+        assert_eq!(image.blocks_free(), 663);
+    }
+
+    #[test]
+    fn test_sizes_with_error_info() {
+        assert_eq!(DiskFormat::from_size(175531), Some(DiskFormat::D64));
+        assert_eq!(DiskFormat::from_size(535331), Some(DiskFormat::D80));
+        assert_eq!(DiskFormat::from_size(1070662), Some(DiskFormat::D82));
+        assert_eq!(DiskFormat::from_size(533249), None);
+    }
+
+    #[test]
+    fn test_save_names_are_exact() {
+        let mut image = DiskImage::blank(DiskFormat::D64, b"TEST", b"01");
+        image.write_file(b"AB", FileType::Prg, &[1]).unwrap();
+        image.write_file(b"A", FileType::Prg, &[2]).unwrap();
+        assert_eq!(
+            image.replace_file(b"A*", FileType::Prg, &[3]),
+            Err(DosError::SyntaxError)
+        );
+        let file = image.find_exact(b"AB").unwrap();
+        assert_eq!(image.read_file(&file), [1]);
+        assert_eq!(image.rename(b"A", b"A?"), Err(DosError::SyntaxError));
+    }
+
+    #[test]
+    fn test_full_directory_keeps_blocks() {
+        let mut image = DiskImage::blank(DiskFormat::D64, b"TEST", b"01");
+        for i in 0..144 {
+            let name = format!("F{}", i);
+            image
+                .write_file(name.as_bytes(), FileType::Prg, &[1])
+                .unwrap();
+        }
+        let free = image.blocks_free();
+        assert_eq!(
+            image.write_file(b"MORE", FileType::Prg, &[1]),
+            Err(DosError::DiskFull)
+        );
+        assert_eq!(image.blocks_free(), free);
+    }
+}
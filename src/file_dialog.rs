@@ -1,9 +1,16 @@
-use std::fs;
+use crate::cbm_dos::petscii_name;
+use crate::t64::T64Archive;
+use std::fs::{self, File};
+use std::io::Read;
 use std::path::Path;
 
+const PC64_SIGNATURE: &[u8] = b"C64File\0";
+const PC64_HEADER_SIZE: usize = 26;
+
 pub struct FileDialog {
     current_dir: String,
     entries: Vec<String>,
+    labels: Vec<String>,
     selected_index: usize,
     visible: bool,
 }
@@ -13,6 +20,7 @@ impl FileDialog {
         let mut dialog = Self {
             current_dir: default_dir.to_string(),
             entries: Vec::new(),
+            labels: Vec::new(),
             selected_index: 0,
             visible: false,
         };
@@ -48,12 +56,15 @@ impl FileDialog {
     pub fn select_current(&mut self) -> Option<String> {
         if let Some(entry) = self.entries.get(self.selected_index) {
             let path = Path::new(&self.current_dir).join(entry);
-            if path.is_dir() {
+            if is_archive(Path::new(&self.current_dir)) {
+                self.hide();
+                Some(path.to_string_lossy().to_string())
+            } else if path.is_dir() || is_archive(&path) {
                 self.current_dir = path.to_string_lossy().to_string();
                 self.selected_index = 0;
                 self.refresh_entries();
                 None
-            } else if path.is_file() && entry.ends_with(".prg") {
+            } else if path.is_file() && is_supported_file(entry) {
                 self.hide();
                 Some(path.to_string_lossy().to_string())
             } else {
@@ -75,17 +86,34 @@ impl FileDialog {
 
     fn refresh_entries(&mut self) {
         self.entries.clear();
-        if let Ok(entries) = fs::read_dir(&self.current_dir) {
+        self.labels.clear();
+        if is_archive(Path::new(&self.current_dir)) {
+            if let Ok(archive) = T64Archive::open(Path::new(&self.current_dir)) {
+                for (i, entry) in archive.entries.iter().enumerate() {
+                    self.entries.push(i.to_string());
+                    self.labels
+                        .push(format!("{:<16}  ${:04X}", entry.name, entry.load_address));
+                }
+            }
+        } else if let Ok(entries) = fs::read_dir(&self.current_dir) {
             let mut files: Vec<String> = entries
                 .filter_map(|e| e.ok())
                 .filter(|e| {
                     let path = e.path();
                     let name = e.file_name().to_string_lossy().to_string();
-                    path.is_dir() || name.ends_with(".prg")
+                    path.is_dir() || is_supported_file(&name)
                 })
                 .map(|e| e.file_name().to_string_lossy().to_string())
                 .collect();
             files.sort();
+            let current_dir = Path::new(&self.current_dir);
+            self.labels = files
+                .iter()
+                .map(|name| match pc64_name(&current_dir.join(name)) {
+                    Some(cbm_name) => format!("{}  \"{}\"", name, cbm_name),
+                    None => name.clone(),
+                })
+                .collect();
             self.entries = files;
         }
         if self.selected_index >= self.entries.len() {
@@ -97,8 +125,8 @@ impl FileDialog {
         &self.current_dir
     }
 
-    pub fn entries(&self) -> &[String] {
-        &self.entries
+    pub fn labels(&self) -> &[String] {
+        &self.labels
     }
 
     pub fn selected_index(&self) -> usize {
@@ -106,8 +134,59 @@ impl FileDialog {
     }
 }
 
+fn is_archive(path: &Path) -> bool {
+    path.is_file() && path.to_string_lossy().to_lowercase().ends_with(".t64")
+}
+
+fn pc64_kind(name: &str) -> Option<char> {
+    let (_, ext) = name.rsplit_once('.')?;
+    let mut chars = ext.chars();
+    let kind = chars.next()?.to_ascii_lowercase();
+    let digits: Vec<char> = chars.collect();
+    (matches!(kind, 'p' | 's') && digits.len() == 2 && digits.iter().all(|c| c.is_ascii_digit()))
+        .then_some(kind)
+}
+
+fn is_pc64_file(name: &str) -> bool {
+    pc64_kind(name).is_some()
+}
+
+fn is_supported_file(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    [".prg", ".d64", ".d80", ".d82", ".tap", ".t64"]
+        .iter()
+        .any(|extension| lower.ends_with(extension))
+        || is_pc64_file(name)
+}
+
+fn pc64_payload(data: &[u8]) -> Option<(String, &[u8])> {
+    if data.len() >= PC64_HEADER_SIZE && data.starts_with(PC64_SIGNATURE) {
+        Some((petscii_name(&data[8..24]), &data[PC64_HEADER_SIZE..]))
+    } else {
+        None
+    }
+}
+
+fn pc64_name(path: &Path) -> Option<String> {
+    if !is_pc64_file(&path.to_string_lossy()) {
+        return None;
+    }
+    let mut header = [0u8; PC64_HEADER_SIZE];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+    pc64_payload(&header).map(|(name, _)| name)
+}
+
 pub fn load_prg_file(path: &str) -> Result<(u16, Vec<u8>), Box<dyn std::error::Error>> {
-    let data = fs::read(path)?;
+    let path = Path::new(path);
+    if let Some(archive) = path.parent().filter(|p| is_archive(p)) {
+        let index = path.file_name().unwrap_or_default().to_string_lossy();
+        return T64Archive::open(archive)?.load(index.parse()?);
+    }
+    if pc64_kind(&path.to_string_lossy()) == Some('s') {
+        return Err(format!("'{}' is a sequential file, not a program", path.display()).into());
+    }
+    let file = fs::read(path)?;
+    let data = pc64_payload(&file).map_or(&file[..], |(_, payload)| payload);
     if data.len() < 2 {
         return Err("PRG file too small".into());
     }
@@ -133,4 +212,21 @@ mod tests {
         dialog.hide();
         assert!(!dialog.is_visible());
     }
+
+    #[test]
+    fn test_pc64_payload() {
+        let mut data = b"C64File\0HELLO".to_vec();
+        data.resize(PC64_HEADER_SIZE, 0);
+        data.extend_from_slice(&[0x01, 0x04, 0xEA]);
+        let (name, payload) = pc64_payload(&data).unwrap();
+        assert_eq!(name, "HELLO");
+        assert_eq!(payload, &[0x01, 0x04, 0xEA]);
+        assert!(pc64_payload(&[0x01, 0x04, 0xEA]).is_none());
+        assert!(is_pc64_file("GAME.P00"));
+        assert!(is_pc64_file("notes.s01"));
+        assert!(!is_pc64_file("game.prg"));
+        assert!(!is_pc64_file("game.p1"));
+        assert!(!is_pc64_file("game.r00"));
+        assert!(load_prg_file("notes.s01").is_err());
+    }
 }
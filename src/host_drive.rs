@@ -0,0 +1,296 @@
+use crate::cbm_dos::{
+    matches_pattern, AccessMode, DirectoryEntry, DirectoryListing, DosDevice, DosError, DosStorage,
+    FileType,
+};
+use std::fs;
+use std::path::PathBuf;
+
+struct HostFile {
+    path: PathBuf,
+    entry: DirectoryEntry,
+}
+
+pub struct HostStorage {
+    root: PathBuf,
+}
+
+pub type HostDrive = DosDevice<HostStorage>;
+
+impl HostDrive {
+    pub fn new(device_number: u8, root: &str) -> Self {
+        let storage = HostStorage {
+            root: PathBuf::from(root),
+        };
+        DosDevice::with_storage(device_number, "HOST FS DRIVE", storage)
+    }
+}
+
+impl HostStorage {
+    fn files(&self) -> Vec<HostFile> {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return Vec::new();
+        };
+        let mut files: Vec<HostFile> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+                let file_type = extension_type(&path.extension()?.to_string_lossy())?;
+                let stem = path.file_stem()?.to_str()?;
+                if !stem.is_ascii() {
+                    return None;
+                }
+                let name = stem.to_ascii_uppercase().into_bytes();
+                Some(HostFile {
+                    entry: DirectoryEntry {
+                        name,
+                        file_type,
+                        blocks: metadata.len().div_ceil(254).min(u16::MAX as u64) as u16,
+                        closed: true,
+                        locked: metadata.permissions().readonly(),
+                    },
+                    path,
+                })
+            })
+            .collect();
+        files.sort_by(|a, b| a.entry.name.cmp(&b.entry.name));
+        files
+    }
+
+    fn blocks_free(&self) -> u16 {
+        fs2::available_space(&self.root).map_or(0, blocks_for_bytes)
+    }
+
+    fn find_file(&self, pattern: &[u8], file_type: Option<FileType>) -> Option<HostFile> {
+        self.files().into_iter().find(|f| {
+            matches_pattern(pattern, &f.entry.name)
+                && file_type.is_none_or(|t| t == f.entry.file_type)
+        })
+    }
+
+    fn host_path(&self, name: &[u8], file_type: FileType) -> Result<PathBuf, DosError> {
+        let extension = match file_type {
+            FileType::Prg => "prg",
+            FileType::Seq => "seq",
+            _ => return Err(DosError::FileTypeMismatch),
+        };
+        if name.iter().any(|&b| b == b'*' || b == b'?') {
+            return Err(DosError::SyntaxError);
+        }
+        let stem: String = name
+            .iter()
+            .map(|&b| match b {
+                b'/' | b'\\' | b':' | b'.' => '_',
+                0x20..=0x7E => (b as char).to_ascii_lowercase(),
+                _ => '_',
+            })
+            .collect();
+        Ok(self.root.join(format!("{}.{}", stem, extension)))
+    }
+}
+
+impl DosStorage for HostStorage {
+    type Target = PathBuf;
+
+    fn directory(&self, argument: &[u8]) -> Result<Vec<u8>, DosError> {
+        let pattern = match argument.iter().position(|&b| b == b':') {
+            Some(colon) => &argument[colon + 1..],
+            None => b"*".as_slice(),
+        };
+        let root_name = self
+            .root
+            .canonicalize()
+            .ok()
+            .and_then(|path| path.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+
+        let mut listing = DirectoryListing::new();
+        listing.add_header(0, root_name.as_bytes(), b"HOST");
+        for file in self.files() {
+            if matches_pattern(pattern, &file.entry.name) {
+                listing.add_entry(&file.entry);
+            }
+        }
+        listing.add_blocks_free(self.blocks_free());
+        Ok(listing.finish())
+    }
+
+    fn read_file(
+        &self,
+        _drive: u8,
+        name: &[u8],
+        file_type: Option<FileType>,
+    ) -> Result<Vec<u8>, DosError> {
+        let file = self
+            .find_file(name, file_type)
+            .ok_or(DosError::FileNotFound)?;
+        fs::read(&file.path).map_err(|_| DosError::DriveNotReady)
+    }
+
+    fn create_file(
+        &mut self,
+        _drive: u8,
+        name: &[u8],
+        file_type: FileType,
+        mode: AccessMode,
+        replace: bool,
+    ) -> Result<(PathBuf, Vec<u8>), DosError> {
+        let existing = self.find_file(name, Some(file_type));
+        match (mode, existing) {
+            (AccessMode::Append, Some(file)) => {
+                let data = fs::read(&file.path).map_err(|_| DosError::DriveNotReady)?;
+                Ok((file.path, data))
+            }
+            (AccessMode::Append, None) => Err(DosError::FileNotFound),
+            (_, Some(_)) if !replace => Err(DosError::FileExists),
+            (_, Some(file)) => Ok((file.path, Vec::new())),
+            (_, None) => Ok((self.host_path(name, file_type)?, Vec::new())),
+        }
+    }
+
+    fn write_file(&mut self, path: PathBuf, data: &[u8]) -> Result<(), DosError> {
+        fs::write(path, data).map_err(|_| DosError::WriteError)
+    }
+
+    fn scratch(&mut self, _drive: u8, pattern: &[u8]) -> Result<u8, DosError> {
+        let mut count = 0u8;
+        for file in self.files() {
+            if file.entry.locked || !matches_pattern(pattern, &file.entry.name) {
+                continue;
+            }
+            fs::remove_file(&file.path).map_err(|_| DosError::WriteError)?;
+            count = count.saturating_add(1);
+        }
+        Ok(count)
+    }
+
+    fn rename(&mut self, _drive: u8, old: &[u8], new: &[u8]) -> Result<(), DosError> {
+        if self.find_file(new, None).is_some() {
+            return Err(DosError::FileExists);
+        }
+        let file = self.find_file(old, None).ok_or(DosError::FileNotFound)?;
+        let path = self.host_path(new, file.entry.file_type)?;
+        fs::rename(&file.path, path).map_err(|_| DosError::WriteError)
+    }
+
+    fn command(&mut self, _drive: u8, command: &[u8], _argument: &[u8]) -> Result<(), DosError> {
+        match command[0] {
+            b'I' | b'V' => Ok(()),
+            _ => Err(DosError::InvalidCommand),
+        }
+    }
+}
+
+fn blocks_for_bytes(bytes: u64) -> u16 {
+    (bytes / 254).min(u16::MAX as u64) as u16
+}
+
+fn extension_type(extension: &str) -> Option<FileType> {
+    match extension.to_ascii_lowercase().as_str() {
+        "prg" => Some(FileType::Prg),
+        "seq" => Some(FileType::Seq),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ieee488::Ieee488Device;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("pet4032_host_drive_{}", name));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn save(drive: &mut HostDrive, name: &[u8], data: &[u8]) {
+        drive.open(1, name);
+        for &byte in data {
+            drive.write_byte(1, byte);
+        }
+        drive.close(1);
+    }
+
+    fn load(drive: &mut HostDrive, name: &[u8]) -> Vec<u8> {
+        drive.open(0, name);
+        let mut data = Vec::new();
+        while let Some((byte, eoi)) = drive.read_byte(0) {
+            data.push(byte);
+            if eoi {
+                break;
+            }
+        }
+        drive.close(0);
+        data
+    }
+
+    fn status(drive: &mut HostDrive) -> Vec<u8> {
+        let mut message = Vec::new();
+        loop {
+            let (byte, eoi) = drive.read_byte(15).unwrap();
+            message.push(byte);
+            if eoi {
+                return message;
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_load_and_directory() {
+        let root = temp_root("round_trip");
+        let mut drive = HostDrive::new(10, root.to_str().unwrap());
+        let program = [0x01, 0x04, 0x0B, 0x04, 0x0A, 0x00];
+
+        save(&mut drive, b"HELLO", &program);
+        assert_eq!(fs::read(root.join("hello.prg")).unwrap(), program);
+        assert_eq!(load(&mut drive, b"HELLO"), program);
+        assert_eq!(load(&mut drive, b"H*"), program);
+
+        save(&mut drive, b"HELLO", &[0x01, 0x04]);
+        assert_eq!(status(&mut drive), b"63,FILE EXISTS,00,00\r");
+        save(&mut drive, b"@0:HELLO", &[0x01, 0x04]);
+        assert_eq!(load(&mut drive, b"HELLO"), [0x01, 0x04]);
+
+        let listing = load(&mut drive, b"$");
+        let entry = b"\x01\x00   \"HELLO\"            PRG ";
+        assert!(listing.windows(entry.len()).any(|window| window == entry));
+        let free_line = &listing[listing.len() - 32..listing.len() - 28];
+        let free = blocks_for_bytes(fs2::available_space(&root).unwrap());
+        assert_eq!(&free_line[2..], &free.to_le_bytes());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_names_stay_inside_root() {
+        let root = temp_root("sanitise");
+        let mut drive = HostDrive::new(10, root.to_str().unwrap());
+
+        save(&mut drive, b"../ESCAPE", &[0x01, 0x04]);
+        save(&mut drive, b"0:A\\B", &[0x01, 0x04]);
+        save(&mut drive, b"WILD*", &[0x01, 0x04]);
+        assert_eq!(status(&mut drive), b"30,SYNTAX ERROR,00,00\r");
+
+        let mut names: Vec<_> = fs::read_dir(&root)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["___escape.prg", "a_b.prg"]);
+        assert!(!root.parent().unwrap().join("escape.prg").exists());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_blocks_for_bytes() {
+        assert_eq!(blocks_for_bytes(0), 0);
+        assert_eq!(blocks_for_bytes(253), 0);
+        assert_eq!(blocks_for_bytes(254), 1);
+        assert_eq!(blocks_for_bytes(254 * 664 + 100), 664);
+        assert_eq!(blocks_for_bytes(u64::MAX), 65535);
+    }
+}
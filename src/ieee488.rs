@@ -0,0 +1,431 @@
+use std::ops::BitOr;
+use std::path::Path;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Ieee488Lines {
+    pub data: u8,
+    pub atn: bool,
+    pub eoi: bool,
+    pub dav: bool,
+    pub nrfd: bool,
+    pub ndac: bool,
+    pub srq: bool,
+    pub ifc: bool,
+    pub ren: bool,
+}
+
+impl BitOr for Ieee488Lines {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            data: self.data | rhs.data,
+            atn: self.atn || rhs.atn,
+            eoi: self.eoi || rhs.eoi,
+            dav: self.dav || rhs.dav,
+            nrfd: self.nrfd || rhs.nrfd,
+            ndac: self.ndac || rhs.ndac,
+            srq: self.srq || rhs.srq,
+            ifc: self.ifc || rhs.ifc,
+            ren: self.ren || rhs.ren,
+        }
+    }
+}
+
+pub trait Ieee488Device {
+    fn device_number(&self) -> u8;
+    fn open(&mut self, secondary: u8, name: &[u8]);
+    fn close(&mut self, secondary: u8);
+    fn write_byte(&mut self, secondary: u8, byte: u8);
+    fn read_byte(&mut self, secondary: u8) -> Option<(u8, bool)>;
+    fn unlisten(&mut self, _secondary: u8) {}
+
+    fn mount(&mut self, _drive: u8, _path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        Err("Device does not accept media".into())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum AcceptorState {
+    Ready,
+    Accepted,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SourceState {
+    Idle,
+    WaitReady,
+    Valid,
+}
+
+struct AttachedDevice {
+    device: Box<dyn Ieee488Device>,
+    listening: bool,
+    talking: bool,
+    secondary: Option<u8>,
+    opening: Option<u8>,
+    open_name: Vec<u8>,
+    acceptor: AcceptorState,
+    source: SourceState,
+    pending: [Option<(u8, bool)>; 16],
+    out: Ieee488Lines,
+}
+
+impl AttachedDevice {
+    fn new(device: Box<dyn Ieee488Device>) -> Self {
+        Self {
+            device,
+            listening: false,
+            talking: false,
+            secondary: None,
+            opening: None,
+            open_name: Vec::new(),
+            acceptor: AcceptorState::Ready,
+            source: SourceState::Idle,
+            pending: [None; 16],
+            out: Ieee488Lines::default(),
+        }
+    }
+
+    fn update(&mut self, lines: Ieee488Lines) {
+        if lines.ifc {
+            self.listening = false;
+            self.talking = false;
+            self.secondary = None;
+            self.opening = None;
+            self.acceptor = AcceptorState::Ready;
+            self.source = SourceState::Idle;
+            self.out = Ieee488Lines::default();
+            return;
+        }
+
+        if lines.atn {
+            self.out.data = 0;
+            self.out.dav = false;
+            self.out.eoi = false;
+            self.source = SourceState::Idle;
+            self.accept(lines, true);
+        } else if self.listening {
+            self.accept(lines, false);
+        } else if self.talking {
+            self.send(lines);
+        } else {
+            self.acceptor = AcceptorState::Ready;
+            self.out = Ieee488Lines::default();
+        }
+    }
+
+    fn accept(&mut self, lines: Ieee488Lines, atn: bool) {
+        match self.acceptor {
+            AcceptorState::Ready => {
+                self.out.ndac = true;
+                self.out.nrfd = false;
+                if lines.dav {
+                    self.out.nrfd = true;
+                    self.receive(lines.data, atn);
+                    self.out.ndac = false;
+                    self.acceptor = AcceptorState::Accepted;
+                }
+            }
+            AcceptorState::Accepted => {
+                if !lines.dav {
+                    self.out.ndac = true;
+                    self.out.nrfd = false;
+                    self.acceptor = AcceptorState::Ready;
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, lines: Ieee488Lines) {
+        let sa = match self.secondary {
+            Some(sa) => sa as usize,
+            None => {
+                self.out = Ieee488Lines::default();
+                return;
+            }
+        };
+
+        match self.source {
+            SourceState::Idle => {
+                self.out = Ieee488Lines::default();
+                if self.pending[sa].is_none() {
+                    self.pending[sa] = self.device.read_byte(sa as u8);
+                }
+                if let Some((byte, eoi)) = self.pending[sa] {
+                    self.out.data = byte;
+                    self.out.eoi = eoi;
+                    self.source = SourceState::WaitReady;
+                }
+            }
+            SourceState::WaitReady => {
+                if !lines.nrfd && lines.ndac {
+                    self.out.dav = true;
+                    self.source = SourceState::Valid;
+                }
+            }
+            SourceState::Valid => {
+                if !lines.ndac && lines.nrfd {
+                    self.pending[sa] = None;
+                    self.out = Ieee488Lines::default();
+                    self.source = SourceState::Idle;
+                } else if !lines.ndac {
+                    self.out.dav = false;
+                    self.source = SourceState::WaitReady;
+                }
+            }
+        }
+    }
+
+    fn receive(&mut self, byte: u8, atn: bool) {
+        if atn {
+            self.command(byte);
+        } else if self.opening.is_some() {
+            self.open_name.push(byte);
+        } else if let Some(sa) = self.secondary {
+            self.device.write_byte(sa, byte);
+        }
+    }
+
+    fn command(&mut self, byte: u8) {
+        let number = self.device.device_number();
+        match byte {
+            0x3F => {
+                if self.listening {
+                    self.finish_listen();
+                }
+                self.listening = false;
+            }
+            0x5F => {
+                self.talking = false;
+                self.secondary = None;
+            }
+            0x20..=0x3E if byte & 0x1F == number => {
+                self.listening = true;
+                self.talking = false;
+                self.secondary = None;
+            }
+            0x40..=0x5E => {
+                if byte & 0x1F == number {
+                    self.talking = true;
+                    self.listening = false;
+                } else {
+                    self.talking = false;
+                }
+                self.secondary = None;
+            }
+            0x60..=0x7F if self.listening || self.talking => {
+                self.secondary = Some(byte & 0x0F);
+            }
+            0xE0..=0xEF if self.listening || self.talking => {
+                let sa = byte & 0x0F;
+                self.pending[sa as usize] = None;
+                self.device.close(sa);
+                self.secondary = None;
+            }
+            0xF0..=0xFF if self.listening => {
+                let sa = byte & 0x0F;
+                self.pending[sa as usize] = None;
+                self.opening = Some(sa);
+                self.open_name.clear();
+                self.secondary = Some(sa);
+            }
+            _ => {}
+        }
+    }
+
+    fn finish_listen(&mut self) {
+        if let Some(sa) = self.opening.take() {
+            self.device.open(sa, &self.open_name);
+            self.open_name.clear();
+        } else if let Some(sa) = self.secondary {
+            self.device.unlisten(sa);
+        }
+        self.secondary = None;
+    }
+}
+
+pub struct Ieee488Bus {
+    devices: Vec<AttachedDevice>,
+}
+
+impl Ieee488Bus {
+    pub fn new() -> Self {
+        Self {
+            devices: Vec::new(),
+        }
+    }
+
+    pub fn attach(&mut self, device: Box<dyn Ieee488Device>) {
+        let number = device.device_number();
+        self.devices.retain(|d| d.device.device_number() != number);
+        self.devices.push(AttachedDevice::new(device));
+    }
+
+    pub fn device_mut(&mut self, number: u8) -> Option<&mut (dyn Ieee488Device + 'static)> {
+        self.devices
+            .iter_mut()
+            .find(|d| d.device.device_number() == number)
+            .map(|d| d.device.as_mut())
+    }
+
+    pub fn update(&mut self, controller: Ieee488Lines) -> Ieee488Lines {
+        let previous: Vec<Ieee488Lines> = self.devices.iter().map(|d| d.out).collect();
+
+        let mut result = controller;
+        for (i, device) in self.devices.iter_mut().enumerate() {
+            let lines = previous
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .fold(controller, |lines, (_, &out)| lines | out);
+            device.update(lines);
+            result = result | device.out;
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct Log {
+        opened: Vec<(u8, Vec<u8>)>,
+        written: Vec<(u8, u8)>,
+        closed: Vec<u8>,
+    }
+
+    struct TestDevice {
+        log: Rc<RefCell<Log>>,
+        data: Vec<u8>,
+    }
+
+    impl Ieee488Device for TestDevice {
+        fn device_number(&self) -> u8 {
+            8
+        }
+
+        fn open(&mut self, secondary: u8, name: &[u8]) {
+            self.log
+                .borrow_mut()
+                .opened
+                .push((secondary, name.to_vec()));
+        }
+
+        fn close(&mut self, secondary: u8) {
+            self.log.borrow_mut().closed.push(secondary);
+        }
+
+        fn write_byte(&mut self, secondary: u8, byte: u8) {
+            self.log.borrow_mut().written.push((secondary, byte));
+        }
+
+        fn read_byte(&mut self, _secondary: u8) -> Option<(u8, bool)> {
+            if self.data.is_empty() {
+                None
+            } else {
+                let byte = self.data.remove(0);
+                Some((byte, self.data.is_empty()))
+            }
+        }
+    }
+
+    pub(crate) fn send(bus: &mut Ieee488Bus, byte: u8, atn: bool) {
+        let mut controller = Ieee488Lines {
+            atn,
+            ..Ieee488Lines::default()
+        };
+        let lines = bus.update(controller);
+        assert!(lines.ndac, "no listener acknowledged");
+        controller.data = byte;
+        controller.dav = true;
+        let lines = bus.update(controller);
+        assert!(!lines.ndac);
+        controller.data = 0;
+        controller.dav = false;
+        bus.update(controller);
+    }
+
+    pub(crate) fn receive(bus: &mut Ieee488Bus) -> (u8, bool) {
+        let mut controller = Ieee488Lines {
+            ndac: true,
+            nrfd: true,
+            ..Ieee488Lines::default()
+        };
+        let mut lines = bus.update(controller);
+        controller.nrfd = false;
+        while !lines.dav {
+            lines = bus.update(controller);
+        }
+        let received = (lines.data, lines.eoi);
+        controller.nrfd = true;
+        controller.ndac = false;
+        bus.update(controller);
+        controller.ndac = true;
+        bus.update(controller);
+        received
+    }
+
+    #[test]
+    fn test_open_and_write() {
+        let log = Rc::new(RefCell::new(Log::default()));
+        let mut bus = Ieee488Bus::new();
+        bus.attach(Box::new(TestDevice {
+            log: log.clone(),
+            data: Vec::new(),
+        }));
+
+        send(&mut bus, 0x28, true);
+        send(&mut bus, 0xF1, true);
+        send(&mut bus, b'A', false);
+        send(&mut bus, 0x3F, true);
+        send(&mut bus, 0x28, true);
+        send(&mut bus, 0x61, true);
+        send(&mut bus, 0x42, false);
+        send(&mut bus, 0x3F, true);
+        send(&mut bus, 0x28, true);
+        send(&mut bus, 0xE1, true);
+        send(&mut bus, 0x3F, true);
+
+        let log = log.borrow();
+        assert_eq!(log.opened, vec![(1, b"A".to_vec())]);
+        assert_eq!(log.written, vec![(1, 0x42)]);
+        assert_eq!(log.closed, vec![1]);
+    }
+
+    #[test]
+    fn test_talk() {
+        let log = Rc::new(RefCell::new(Log::default()));
+        let mut bus = Ieee488Bus::new();
+        bus.attach(Box::new(TestDevice {
+            log,
+            data: vec![0x01, 0x04],
+        }));
+
+        send(&mut bus, 0x48, true);
+        send(&mut bus, 0x60, true);
+        assert_eq!(receive(&mut bus), (0x01, false));
+        assert_eq!(receive(&mut bus), (0x04, true));
+    }
+
+    #[test]
+    fn test_other_device_not_listening() {
+        let log = Rc::new(RefCell::new(Log::default()));
+        let mut bus = Ieee488Bus::new();
+        bus.attach(Box::new(TestDevice {
+            log: log.clone(),
+            data: Vec::new(),
+        }));
+
+        send(&mut bus, 0x29, true);
+        send(&mut bus, 0x61, true);
+        let lines = bus.update(Ieee488Lines::default());
+        assert!(!lines.ndac && !lines.nrfd);
+        assert!(log.borrow().written.is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+use crate::bus::PetBus;
+use mos6502::bus::Bus;
+use mos6502::cpu::Cpu;
+
+const LOAD_ENTRY: u16 = 0xF356;
+const SAVE_ENTRY: u16 = 0xF6E3;
+const ERROR_ENTRY: u16 = 0xF5AF;
+const FILE_NOT_FOUND: u8 = 0x24;
+
+const STATUS: u16 = 0x96;
+const VERIFY_FLAG: u16 = 0x9D;
+const END_ADDRESS: u16 = 0xC9;
+const FILENAME_LENGTH: u16 = 0xD1;
+const DEVICE: u16 = 0xD4;
+const FILENAME_ADDRESS: u16 = 0xDA;
+const POINTER: u16 = 0xFB;
+
+const STATUS_EOI: u8 = 0x40;
+const STATUS_VERIFY_ERROR: u8 = 0x10;
+const STATUS_TIMEOUT_READ: u8 = 0x02;
+
+pub fn service(cpu: &mut Cpu<PetBus>) {
+    match cpu.pc {
+        LOAD_ENTRY if entry_matches(&mut cpu.bus, LOAD_ENTRY) => load(cpu),
+        SAVE_ENTRY if entry_matches(&mut cpu.bus, SAVE_ENTRY) => save(cpu),
+        _ => {}
+    }
+}
+
+fn entry_matches(bus: &mut PetBus, address: u16) -> bool {
+    bus.read(address) == 0xA5 && bus.read(address + 1) == DEVICE as u8
+}
+
+fn read_word(bus: &mut PetBus, address: u16) -> u16 {
+    u16::from_le_bytes([bus.read(address), bus.read(address + 1)])
+}
+
+fn write_word(bus: &mut PetBus, address: u16, value: u16) {
+    let [low, high] = value.to_le_bytes();
+    bus.write(address, low);
+    bus.write(address + 1, high);
+}
+
+fn filename(bus: &mut PetBus) -> Vec<u8> {
+    let length = bus.read(FILENAME_LENGTH);
+    let address = read_word(bus, FILENAME_ADDRESS);
+    (0..length as u16)
+        .map(|i| bus.read(address.wrapping_add(i)))
+        .collect()
+}
+
+fn return_from_subroutine(cpu: &mut Cpu<PetBus>) {
+    let low = cpu.bus.read(0x0100 + cpu.sp.wrapping_add(1) as u16);
+    let high = cpu.bus.read(0x0100 + cpu.sp.wrapping_add(2) as u16);
+    cpu.sp = cpu.sp.wrapping_add(2);
+    cpu.pc = u16::from_le_bytes([low, high]).wrapping_add(1);
+}
+
+fn load(cpu: &mut Cpu<PetBus>) {
+    let device_number = cpu.bus.read(DEVICE);
+    let name = filename(&mut cpu.bus);
+    if device_number < 4 || name.is_empty() {
+        return;
+    }
+    let Some(device) = cpu.bus.ieee.device_mut(device_number) else {
+        return;
+    };
+
+    device.open(0, &name);
+    let mut data = Vec::new();
+    while let Some((byte, eoi)) = device.read_byte(0) {
+        data.push(byte);
+        if eoi {
+            break;
+        }
+    }
+    device.close(0);
+
+    if data.len() < 2 {
+        cpu.bus.write(STATUS, STATUS_TIMEOUT_READ);
+        cpu.y = FILE_NOT_FOUND;
+        cpu.pc = ERROR_ENTRY;
+        return;
+    }
+
+    let verify = cpu.bus.read(VERIFY_FLAG) != 0;
+    let start = u16::from_le_bytes([data[0], data[1]]);
+    let mut status = STATUS_EOI;
+    let mut address = start;
+    for &byte in &data[2..] {
+        if verify {
+            if cpu.bus.read(address) != byte {
+                status |= STATUS_VERIFY_ERROR;
+            }
+        } else {
+            cpu.bus.write(address, byte);
+        }
+        address = address.wrapping_add(1);
+    }
+
+    cpu.bus.write(STATUS, status);
+    write_word(&mut cpu.bus, POINTER, start);
+    write_word(&mut cpu.bus, END_ADDRESS, address);
+    return_from_subroutine(cpu);
+}
+
+fn save(cpu: &mut Cpu<PetBus>) {
+    let device_number = cpu.bus.read(DEVICE);
+    let name = filename(&mut cpu.bus);
+    if device_number < 4 || name.is_empty() {
+        return;
+    }
+    let start = read_word(&mut cpu.bus, POINTER);
+    let end = read_word(&mut cpu.bus, END_ADDRESS);
+    let mut data = start.to_le_bytes().to_vec();
+    let mut address = start;
+    while address != end {
+        data.push(cpu.bus.read(address));
+        address = address.wrapping_add(1);
+    }
+
+    let Some(device) = cpu.bus.ieee.device_mut(device_number) else {
+        return;
+    };
+    device.open(1, &name);
+    for byte in data {
+        device.write_byte(1, byte);
+    }
+    device.unlisten(1);
+    device.close(1);
+
+    cpu.bus.write(STATUS, 0);
+    return_from_subroutine(cpu);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ieee488::Ieee488Device;
+    use crate::machine::Model;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    type Files = Rc<RefCell<HashMap<Vec<u8>, Vec<u8>>>>;
+
+    struct TestDrive {
+        files: Files,
+        name: Vec<u8>,
+        data: Vec<u8>,
+    }
+
+    impl Ieee488Device for TestDrive {
+        fn device_number(&self) -> u8 {
+            8
+        }
+
+        fn open(&mut self, _secondary: u8, name: &[u8]) {
+            self.name = name.to_vec();
+            self.data = self.files.borrow().get(name).cloned().unwrap_or_default();
+        }
+
+        fn close(&mut self, _secondary: u8) {}
+
+        fn write_byte(&mut self, _secondary: u8, byte: u8) {
+            self.data.push(byte);
+        }
+
+        fn read_byte(&mut self, _secondary: u8) -> Option<(u8, bool)> {
+            if self.data.is_empty() {
+                None
+            } else {
+                let byte = self.data.remove(0);
+                Some((byte, self.data.is_empty()))
+            }
+        }
+
+        fn unlisten(&mut self, _secondary: u8) {
+            self.files
+                .borrow_mut()
+                .insert(self.name.clone(), self.data.clone());
+        }
+    }
+
+    fn machine(files: &Files) -> Cpu<PetBus> {
+        let mut cpu = Cpu::new(PetBus::with_blank_roms(Model::Pet4032));
+        cpu.bus.ieee.attach(Box::new(TestDrive {
+            files: files.clone(),
+            name: Vec::new(),
+            data: Vec::new(),
+        }));
+        cpu.bus.write(DEVICE, 8);
+        cpu.bus.write(FILENAME_LENGTH, 4);
+        write_word(&mut cpu.bus, FILENAME_ADDRESS, 0x0300);
+        for (i, &byte) in b"PROG".iter().enumerate() {
+            cpu.bus.write(0x0300 + i as u16, byte);
+        }
+        cpu.sp = 0xFD;
+        cpu.bus.write(0x01FE, 0x33);
+        cpu.bus.write(0x01FF, 0x12);
+        cpu
+    }
+
+    #[test]
+    fn test_load() {
+        let files = Files::default();
+        files
+            .borrow_mut()
+            .insert(b"PROG".to_vec(), vec![0x01, 0x04, 0xAA, 0xBB, 0xCC]);
+        let mut cpu = machine(&files);
+        write_word(&mut cpu.bus, POINTER, 0x1234);
+        load(&mut cpu);
+
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.bus.read(STATUS), STATUS_EOI);
+        assert_eq!(read_word(&mut cpu.bus, POINTER), 0x0401);
+        assert_eq!(read_word(&mut cpu.bus, END_ADDRESS), 0x0404);
+        assert_eq!(
+            [0x0401, 0x0402, 0x0403].map(|address| cpu.bus.read(address)),
+            [0xAA, 0xBB, 0xCC]
+        );
+
+        cpu.bus.write(0x0402, 0x00);
+        cpu.bus.write(VERIFY_FLAG, 1);
+        cpu.sp = 0xFD;
+        load(&mut cpu);
+        assert_eq!(cpu.bus.read(STATUS), STATUS_EOI | STATUS_VERIFY_ERROR);
+        assert_eq!(cpu.bus.read(0x0402), 0x00);
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let files = Files::default();
+        let mut cpu = machine(&files);
+        load(&mut cpu);
+
+        assert_eq!(cpu.pc, ERROR_ENTRY);
+        assert_eq!(cpu.y, FILE_NOT_FOUND);
+        assert_eq!(cpu.bus.read(STATUS), STATUS_TIMEOUT_READ);
+    }
+
+    #[test]
+    fn test_save() {
+        let files = Files::default();
+        let mut cpu = machine(&files);
+        for (i, byte) in [0x10, 0x20, 0x30].into_iter().enumerate() {
+            cpu.bus.write(0x0401 + i as u16, byte);
+        }
+        write_word(&mut cpu.bus, POINTER, 0x0401);
+        write_word(&mut cpu.bus, END_ADDRESS, 0x0404);
+        cpu.bus.write(STATUS, 0xFF);
+        save(&mut cpu);
+
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.bus.read(STATUS), 0);
+        assert_eq!(read_word(&mut cpu.bus, POINTER), 0x0401);
+        assert_eq!(
+            files.borrow()[b"PROG".as_slice()],
+            [0x01, 0x04, 0x10, 0x20, 0x30]
+        );
+    }
+}
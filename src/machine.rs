@@ -0,0 +1,109 @@
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Model {
+    Pet2001,
+    Pet3032,
+    Pet4032,
+    Pet8032,
+    Pet8096,
+    Pet8296,
+}
+
+impl Model {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "2001" => Some(Model::Pet2001),
+            "3032" => Some(Model::Pet3032),
+            "4032" => Some(Model::Pet4032),
+            "8032" => Some(Model::Pet8032),
+            "8096" => Some(Model::Pet8096),
+            "8296" => Some(Model::Pet8296),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Model::Pet2001 => "Commodore PET 2001",
+            Model::Pet3032 => "Commodore PET 3032",
+            Model::Pet4032 => "Commodore PET 4032",
+            Model::Pet8032 => "Commodore PET 8032",
+            Model::Pet8096 => "Commodore PET 8096",
+            Model::Pet8296 => "Commodore PET 8296",
+        }
+    }
+
+    pub fn basic_version(self) -> u8 {
+        match self {
+            Model::Pet2001 => 1,
+            Model::Pet3032 => 2,
+            _ => 4,
+        }
+    }
+
+    pub fn zero_page(self) -> BasicZeroPage {
+        match self {
+            Model::Pet2001 => BasicZeroPage {
+                status: 0x020C,
+                load_end: None,
+                vartab: 0x7C,
+            },
+            _ => BasicZeroPage {
+                status: 0x96,
+                load_end: Some(0xC9),
+                vartab: 0x2A,
+            },
+        }
+    }
+
+    pub fn editor_rom(self) -> &'static str {
+        match self {
+            Model::Pet2001 => "edit-1-n.901439-03.bin",
+            Model::Pet3032 => "edit-2-n.901447-24.bin",
+            Model::Pet4032 => "edit-4-40-n-60Hz.901499-01.bin",
+            Model::Pet8032 | Model::Pet8096 | Model::Pet8296 => "edit-4-80-b-60Hz.901474-03.bin",
+        }
+    }
+
+    pub fn chars_per_clock(self) -> usize {
+        match self {
+            Model::Pet2001 | Model::Pet3032 | Model::Pet4032 => 1,
+            Model::Pet8032 | Model::Pet8096 | Model::Pet8296 => 2,
+        }
+    }
+
+    pub fn video_ram_size(self) -> usize {
+        match self {
+            Model::Pet2001 | Model::Pet3032 | Model::Pet4032 => 0x400,
+            Model::Pet8032 | Model::Pet8096 | Model::Pet8296 => 0x800,
+        }
+    }
+
+    pub fn ram_size(self) -> usize {
+        match self {
+            Model::Pet2001 => 0x2000,
+            _ => 0x8000,
+        }
+    }
+
+    pub fn has_crtc(self) -> bool {
+        !matches!(self, Model::Pet2001 | Model::Pet3032)
+    }
+
+    pub fn business_keyboard(self) -> bool {
+        matches!(self, Model::Pet8032 | Model::Pet8096 | Model::Pet8296)
+    }
+
+    pub fn memory_expansion(self) -> bool {
+        matches!(self, Model::Pet8096 | Model::Pet8296)
+    }
+
+    pub fn socket_ram(self) -> bool {
+        self == Model::Pet8296
+    }
+}
+
+pub struct BasicZeroPage {
+    pub status: u16,
+    pub load_end: Option<u16>,
+    pub vartab: u16,
+}
@@ -1,47 +1,225 @@
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
+mod audio;
 mod bus;
+mod cbm_dos;
+mod config;
 mod crtc6845;
+mod cycles;
+mod datasette;
+mod disk_drive;
+mod disk_image;
 mod file_dialog;
+mod host_drive;
+mod ieee488;
+mod kernal_trap;
+mod machine;
+mod memory_expansion;
+mod monitor;
+mod opcodes;
 mod pia6821;
 mod renderer;
+mod rom_id;
 mod rom_loader;
+mod t64;
 mod via6522;
+mod wav;
 
+use crate::audio::{AudioSink, SAMPLE_RATE};
 use crate::bus::PetBus;
+use crate::config::{load_profile, Profile, DEFAULT_CONFIG};
+use crate::datasette::Tape;
+use crate::disk_drive::{DiskDrive, DriveModel};
+use crate::disk_image::DiskFormat;
 use crate::file_dialog::{load_prg_file, FileDialog};
+use crate::host_drive::HostDrive;
+use crate::ieee488::Ieee488Device;
+use crate::monitor::Monitor;
 use mos6502::bus::Bus;
 use mos6502::cpu::Cpu;
-use renderer::{draw_file_dialog, draw_pet_screen};
-use rom_loader::load_roms;
+use renderer::{draw_file_dialog, draw_monitor, draw_pet_screen, WindowSize};
+use rom_loader::{load_roms, search_path};
+
+const MAX_QUEUED_AUDIO_BYTES: u32 = SAMPLE_RATE / 5 * 2;
+const MONITOR_FRAME_TIME: Duration = Duration::from_millis(16);
+
+impl AudioSink for AudioQueue<i16> {
+    fn push_samples(&mut self, samples: &[i16]) {
+        if self.size() > MAX_QUEUED_AUDIO_BYTES {
+            return;
+        }
+        if let Err(e) = self.queue_audio(samples) {
+            eprintln!("{}", e);
+        }
+    }
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut config_path = None;
+    let mut profile_name = None;
+    let mut overrides = Profile::default();
+    let mut audio_recording = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                config_path = Some(args.next().ok_or("--config needs a file name")?);
+            }
+            "--profile" => {
+                profile_name = Some(args.next().ok_or("--profile needs a profile name")?);
+            }
+            "--model" => {
+                overrides.model = Some(args.next().ok_or("--model needs a model name")?);
+            }
+            "--ram" => {
+                let size = args.next().ok_or("--ram needs a size in KB")?;
+                overrides.ram_kb = Some(
+                    size.parse()
+                        .map_err(|_| format!("Invalid RAM size: {}", size))?,
+                );
+            }
+            "--rom-9000" => {
+                overrides.rom_9000 = Some(args.next().ok_or("--rom-9000 needs a ROM file")?);
+            }
+            "--rom-a000" => {
+                overrides.rom_a000 = Some(args.next().ok_or("--rom-a000 needs a ROM file")?);
+            }
+            "--ram-9000" => overrides.ram_9000 = Some(true),
+            "--ram-a000" => overrides.ram_a000 = Some(true),
+            "--record-audio" => {
+                audio_recording = Some(args.next().ok_or("--record-audio needs a file name")?);
+            }
+            _ => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
+    let profile = match &config_path {
+        Some(path) => load_profile(Path::new(path), profile_name.as_deref())?,
+        None if Path::new(DEFAULT_CONFIG).exists() => {
+            load_profile(Path::new(DEFAULT_CONFIG), profile_name.as_deref())?
+        }
+        None if profile_name.is_some() => {
+            return Err(format!(
+                "--profile needs a config file ({} not found)",
+                DEFAULT_CONFIG
+            )
+            .into());
+        }
+        None => Profile::default(),
+    };
+    let mut config = profile.merge(overrides).resolve()?;
+    let model = config.model;
+    let software_dir = config.software_dir.as_str();
+
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
     let ttf_context = sdl2::ttf::init()?;
     let window = video_subsystem
-        .window("Commodore PET 4032", 640, 400)
+        .window(
+            model.name(),
+            640 * config.display.scale as u32,
+            400 * config.display.scale as u32,
+        )
         .position_centered()
         .build()?;
 
     let mut canvas = window.into_canvas().build()?;
     let mut event_pump = sdl_context.event_pump()?;
+    let mut window_size = WindowSize::new();
 
-    let roms = load_roms()?;
-    let bus_instance = PetBus::new(roms);
+    let roms = load_roms(model, &config.roms, &search_path(&config.rom_dirs))?;
+    let mut bus_instance = PetBus::new(roms, model, config.ram_size);
+    bus_instance.option_sockets = std::mem::take(&mut config.option_sockets);
+    if let Some(expansion) = &mut bus_instance.expansion {
+        expansion.set_socket_ram(config.socket_ram);
+    }
+    bus_instance.kernal_traps = config.kernal_traps;
+    let keymap = if config.business_keyboard {
+        keycode_to_business_matrix
+    } else {
+        keycode_to_pet_matrix
+    };
+    for drive in &config.drives {
+        let mut device: Box<dyn Ieee488Device> = match drive.kind.as_str() {
+            "host" => Box::new(HostDrive::new(
+                drive.unit,
+                drive.path.as_deref().unwrap_or(software_dir),
+            )),
+            kind => match DriveModel::from_name(kind) {
+                Some(drive_model) => Box::new(DiskDrive::new(drive.unit, drive_model)),
+                None => return Err(format!("Unknown drive type: {}", kind).into()),
+            },
+        };
+        if let Some(path) = drive.path.as_deref().filter(|_| drive.kind != "host") {
+            device.mount(0, Path::new(path))?;
+        }
+        bus_instance.ieee.attach(device);
+    }
+    let audio_spec = AudioSpecDesired {
+        freq: Some(SAMPLE_RATE as i32),
+        channels: Some(1),
+        samples: Some(512),
+    };
+    match sdl_context
+        .audio()
+        .and_then(|audio| audio.open_queue::<i16, _>(None, &audio_spec))
+    {
+        Ok(queue) => {
+            queue.resume();
+            bus_instance.sound.set_sink(Box::new(queue));
+        }
+        Err(e) => eprintln!("Audio unavailable: {}", e),
+    }
+    if let Some(path) = &audio_recording {
+        bus_instance.sound.start_recording(Path::new(path))?;
+    }
     let mut cpu = Cpu::new(bus_instance);
 
     cpu.reset();
 
     let mut last_frame = Instant::now();
-    let cycles_per_frame = 16666;
-    let mut file_dialog = FileDialog::new("./software");
+    let mut file_dialog = FileDialog::new(software_dir);
+    let mut monitor = Monitor::new();
 
     'running: loop {
         for event in event_pump.poll_iter() {
-            if file_dialog.is_visible() {
+            if monitor.is_visible() {
+                match event {
+                    Event::Quit { .. } => break 'running,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape | Keycode::F12),
+                        ..
+                    } => {
+                        monitor.hide();
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Return),
+                        ..
+                    } => {
+                        monitor.execute(&mut cpu, run_instruction);
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Backspace),
+                        ..
+                    } => {
+                        monitor.backspace();
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Up),
+                        ..
+                    } => {
+                        monitor.recall();
+                    }
+                    Event::TextInput { text, .. } => {
+                        monitor.type_text(&text);
+                    }
+                    _ => {}
+                }
+            } else if file_dialog.is_visible() {
                 match event {
                     Event::Quit { .. } => break 'running,
                     Event::KeyDown {
@@ -68,21 +246,32 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                     Event::KeyDown {
                         keycode: Some(Keycode::Return),
+                        keymod,
                         ..
                     } => {
                         if let Some(path) = file_dialog.select_current() {
-                            if let Ok((load_addr, data)) = load_prg_file(&path) {
-                                for (i, byte) in data.iter().enumerate() {
-                                    let addr = load_addr.wrapping_add(i as u16);
-                                    cpu.bus.write(addr, *byte);
+                            let lower = path.to_lowercase();
+                            let deck = shift_index(keymod);
+                            if let Some(format) = DiskFormat::from_path(&path) {
+                                let result = config
+                                    .drive_for(format)
+                                    .and_then(|unit| cpu.bus.ieee.device_mut(unit))
+                                    .map(|d| d.mount(deck as u8, Path::new(&path)));
+                                match result {
+                                    Some(Ok(())) => {}
+                                    Some(Err(e)) => eprintln!("{}", e),
+                                    None => {
+                                        eprintln!("No configured drive accepts {:?} images", format)
+                                    }
+                                }
+                            } else if lower.ends_with(".tap") {
+                                let result = Tape::open(Path::new(&path))
+                                    .and_then(|tape| cpu.bus.datasettes[deck].insert(tape));
+                                if let Err(e) = result {
+                                    eprintln!("{}", e);
                                 }
-                                let end_addr = load_addr.wrapping_add(data.len() as u16);
-                                cpu.bus.write(0x0028, (load_addr & 0xFF) as u8);
-                                cpu.bus.write(0x0029, (load_addr >> 8) as u8);
-                                cpu.bus.write(0x002A, (end_addr & 0xFF) as u8);
-                                cpu.bus.write(0x002B, (end_addr >> 8) as u8);
-                                let run_keys = [(7, 2), (2, 3), (3, 1), (6, 5)];
-                                cpu.bus.pia.auto_type(&run_keys);
+                            } else if let Err(e) = load_program(&mut cpu, &path, keymap) {
+                                eprintln!("{}", e);
                             }
                         }
                     }
@@ -95,15 +284,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     Event::KeyDown {
                         keycode: Some(key), ..
                     } => {
-                        if let Some((row, col)) = keycode_to_pet_matrix(key) {
-                            cpu.bus.pia.set_key(row, col, true);
+                        if let Some((row, col)) = keymap(key) {
+                            cpu.bus.pia1.set_key(row, col, true);
                         }
                     }
                     Event::KeyUp {
                         keycode: Some(key), ..
                     } => {
-                        if let Some((row, col)) = keycode_to_pet_matrix(key) {
-                            cpu.bus.pia.set_key(row, col, false);
+                        if let Some((row, col)) = keymap(key) {
+                            cpu.bus.pia1.set_key(row, col, false);
                         }
                     }
                     _ => {}
@@ -121,18 +310,81 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     } => {
                         file_dialog.show();
                     }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F12),
+                        ..
+                    } => {
+                        monitor.show(&cpu);
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F3),
+                        ..
+                    } => {
+                        cpu.bus.kernal_traps = !cpu.bus.kernal_traps;
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F5),
+                        keymod,
+                        ..
+                    } => {
+                        cpu.bus.datasettes[shift_index(keymod)].play();
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F6),
+                        keymod,
+                        ..
+                    } => {
+                        let datasette = &mut cpu.bus.datasettes[shift_index(keymod)];
+                        let path = new_file_path(software_dir, "recording", "tap");
+                        match datasette.insert(Tape::blank(&path)) {
+                            Ok(()) => datasette.record(),
+                            Err(e) => eprintln!("{}", e),
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F7),
+                        keymod,
+                        ..
+                    } => {
+                        if let Err(e) = cpu.bus.datasettes[shift_index(keymod)].stop() {
+                            eprintln!("{}", e);
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F8),
+                        keymod,
+                        ..
+                    } => {
+                        if let Err(e) = cpu.bus.datasettes[shift_index(keymod)].rewind() {
+                            eprintln!("{}", e);
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F9),
+                        ..
+                    } => {
+                        let result = if cpu.bus.sound.is_recording() {
+                            cpu.bus.sound.stop_recording()
+                        } else {
+                            let path = new_file_path(software_dir, "audio", "wav");
+                            cpu.bus.sound.start_recording(&path)
+                        };
+                        if let Err(e) = result {
+                            eprintln!("{}", e);
+                        }
+                    }
                     Event::KeyDown {
                         keycode: Some(key), ..
                     } => {
-                        if let Some((row, col)) = keycode_to_pet_matrix(key) {
-                            cpu.bus.pia.set_key(row, col, true);
+                        if let Some((row, col)) = keymap(key) {
+                            cpu.bus.pia1.set_key(row, col, true);
                         }
                     }
                     Event::KeyUp {
                         keycode: Some(key), ..
                     } => {
-                        if let Some((row, col)) = keycode_to_pet_matrix(key) {
-                            cpu.bus.pia.set_key(row, col, false);
+                        if let Some((row, col)) = keymap(key) {
+                            cpu.bus.pia1.set_key(row, col, false);
                         }
                     }
                     _ => {}
@@ -140,33 +392,115 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        for _ in 0..cycles_per_frame {
-            cpu.step();
-            cpu.bus.tick();
-
-            if cpu.bus.irq_asserted {
-                cpu.request_irq();
-            } else {
-                cpu.release_irq();
-            }
+        let mut frame_cycles = 0;
+        while !monitor.is_visible() && !cpu.bus.crtc.take_frame_started() {
+            frame_cycles += run_instruction(&mut cpu);
         }
 
-        if file_dialog.is_visible() {
+        if monitor.is_visible() {
+            draw_monitor(&mut canvas, &monitor, &ttf_context);
+        } else if file_dialog.is_visible() {
             draw_file_dialog(&mut canvas, &file_dialog, &ttf_context);
         } else {
-            draw_pet_screen(&mut canvas, &cpu.bus);
+            draw_pet_screen(&mut canvas, &cpu.bus, &config.display, &mut window_size);
         }
 
+        let frame_time = if monitor.is_visible() {
+            MONITOR_FRAME_TIME
+        } else {
+            Duration::from_micros(frame_cycles as u64)
+        };
         let elapsed = last_frame.elapsed();
-        if elapsed < Duration::from_millis(16) {
-            std::thread::sleep(Duration::from_millis(16) - elapsed);
+        if elapsed < frame_time {
+            std::thread::sleep(frame_time - elapsed);
         }
         last_frame = Instant::now();
     }
 
+    for datasette in &mut cpu.bus.datasettes {
+        if let Err(e) = datasette.eject() {
+            eprintln!("{}", e);
+        }
+    }
+    if let Err(e) = cpu.bus.sound.stop_recording() {
+        eprintln!("{}", e);
+    }
+
     Ok(())
 }
 
+fn run_instruction(cpu: &mut Cpu<PetBus>) -> u32 {
+    if cpu.bus.kernal_traps {
+        kernal_trap::service(cpu);
+    }
+    let pc = cpu.pc;
+    let opcode = cpu.bus.read(pc);
+    let interrupt = cpu.bus.irq_asserted && (cpu.status & 0x04) == 0;
+    let penalty = cycles::page_penalty(opcode, pc, cpu.x, cpu.y, |addr| {
+        cpu.bus.peek(addr).unwrap_or(0)
+    });
+    cpu.step();
+    let cycles = if interrupt {
+        cycles::INTERRUPT_CYCLES
+    } else {
+        cycles::instruction_cycles(opcode, pc, cpu.pc) + penalty
+    };
+    for _ in 0..cycles {
+        cpu.bus.tick();
+    }
+
+    if cpu.bus.irq_asserted {
+        cpu.request_irq();
+    } else {
+        cpu.release_irq();
+    }
+    cycles
+}
+
+fn load_program(
+    cpu: &mut Cpu<PetBus>,
+    path: &str,
+    keymap: fn(Keycode) -> Option<(usize, usize)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (load_addr, data) = load_prg_file(path)?;
+    for (i, byte) in data.iter().enumerate() {
+        let addr = load_addr.wrapping_add(i as u16);
+        cpu.bus.write(addr, *byte);
+    }
+    let end_addr = load_addr.wrapping_add(data.len() as u16);
+    let zero_page = cpu.bus.model.zero_page();
+    cpu.bus.write(zero_page.status, 0x40);
+    if let Some(load_end) = zero_page.load_end {
+        cpu.bus.write(load_end, (end_addr & 0xFF) as u8);
+        cpu.bus.write(load_end + 1, (end_addr >> 8) as u8);
+    }
+    if load_addr == 0x0401 {
+        cpu.bus.write(zero_page.vartab, (end_addr & 0xFF) as u8);
+        cpu.bus.write(zero_page.vartab + 1, (end_addr >> 8) as u8);
+        let run_keys: Vec<_> = [Keycode::N, Keycode::U, Keycode::R, Keycode::Return]
+            .into_iter()
+            .filter_map(keymap)
+            .collect();
+        cpu.bus.pia1.auto_type(&run_keys);
+    }
+    Ok(())
+}
+
+fn shift_index(keymod: Mod) -> usize {
+    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+        1
+    } else {
+        0
+    }
+}
+
+fn new_file_path(dir: &str, stem: &str, extension: &str) -> PathBuf {
+    (1..)
+        .map(|n| Path::new(dir).join(format!("{}{:02}.{}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
 fn keycode_to_pet_matrix(key: Keycode) -> Option<(usize, usize)> {
     match key {
         Keycode::Q => Some((2, 0)),
@@ -238,3 +572,71 @@ fn keycode_to_pet_matrix(key: Keycode) -> Option<(usize, usize)> {
         _ => None,
     }
 }
+
+fn keycode_to_business_matrix(key: Keycode) -> Option<(usize, usize)> {
+    match key {
+        Keycode::Num2 => Some((0, 0)),
+        Keycode::Num5 => Some((0, 1)),
+        Keycode::Num8 => Some((0, 2)),
+        Keycode::Minus => Some((0, 3)),
+        Keycode::Right => Some((0, 5)),
+
+        Keycode::Num1 => Some((1, 0)),
+        Keycode::Num4 => Some((1, 1)),
+        Keycode::Num7 => Some((1, 2)),
+        Keycode::Num0 => Some((1, 3)),
+
+        Keycode::S => Some((2, 1)),
+        Keycode::F => Some((2, 2)),
+        Keycode::H => Some((2, 3)),
+        Keycode::RightBracket => Some((2, 4)),
+        Keycode::K => Some((2, 5)),
+        Keycode::Semicolon => Some((2, 6)),
+
+        Keycode::A => Some((3, 0)),
+        Keycode::D => Some((3, 1)),
+        Keycode::G => Some((3, 2)),
+        Keycode::J => Some((3, 3)),
+        Keycode::Return => Some((3, 4)),
+        Keycode::L => Some((3, 5)),
+
+        Keycode::Tab => Some((4, 0)),
+        Keycode::W => Some((4, 1)),
+        Keycode::R => Some((4, 2)),
+        Keycode::Y => Some((4, 3)),
+        Keycode::I => Some((4, 5)),
+        Keycode::P => Some((4, 6)),
+        Keycode::Backspace => Some((4, 7)),
+
+        Keycode::Q => Some((5, 0)),
+        Keycode::E => Some((5, 1)),
+        Keycode::T => Some((5, 2)),
+        Keycode::U => Some((5, 3)),
+        Keycode::Down => Some((5, 4)),
+        Keycode::O => Some((5, 5)),
+        Keycode::LeftBracket => Some((5, 6)),
+
+        Keycode::LShift => Some((6, 0)),
+        Keycode::RShift => Some((6, 6)),
+
+        Keycode::Z => Some((7, 0)),
+        Keycode::C => Some((7, 1)),
+        Keycode::B => Some((7, 2)),
+        Keycode::M => Some((7, 3)),
+        Keycode::Period => Some((7, 5)),
+
+        Keycode::X => Some((8, 1)),
+        Keycode::V => Some((8, 2)),
+        Keycode::N => Some((8, 3)),
+        Keycode::Space => Some((8, 4)),
+        Keycode::Comma => Some((8, 5)),
+        Keycode::Slash => Some((8, 6)),
+
+        Keycode::Num3 => Some((9, 1)),
+        Keycode::Num6 => Some((9, 2)),
+        Keycode::Num9 => Some((9, 3)),
+        Keycode::Equals => Some((9, 5)),
+
+        _ => None,
+    }
+}
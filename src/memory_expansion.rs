@@ -0,0 +1,156 @@
+const ENABLED: u8 = 0x80;
+const IO_PEEK_THROUGH: u8 = 0x40;
+const SCREEN_PEEK_THROUGH: u8 = 0x20;
+const BANK_C000: u8 = 0x08;
+const BANK_8000: u8 = 0x04;
+const PROTECT_C000: u8 = 0x02;
+const PROTECT_8000: u8 = 0x01;
+
+pub struct MemoryExpansion {
+    pub control: u8,
+    ram: Vec<u8>,
+    socket_ram: [Option<Vec<u8>>; 2],
+}
+
+impl MemoryExpansion {
+    pub fn new(socket_ram: [bool; 2]) -> Self {
+        let mut expansion = Self {
+            control: 0,
+            ram: vec![0; 0x10000],
+            socket_ram: [None, None],
+        };
+        expansion.set_socket_ram(socket_ram);
+        expansion
+    }
+
+    pub fn set_socket_ram(&mut self, jumpers: [bool; 2]) {
+        for (ram, jumper) in self.socket_ram.iter_mut().zip(jumpers) {
+            *ram = jumper.then(|| vec![0; 0x1000]);
+        }
+    }
+
+    pub fn has_socket_ram(&self, socket: usize) -> bool {
+        self.socket_ram[socket].is_some()
+    }
+
+    fn bank_index(&self, addr: u16) -> Option<usize> {
+        if (self.control & ENABLED) == 0 || addr < 0x8000 {
+            return None;
+        }
+        if (0x8000..0x9000).contains(&addr) && (self.control & SCREEN_PEEK_THROUGH) != 0 {
+            return None;
+        }
+        if (0xE800..0xF000).contains(&addr) && (self.control & IO_PEEK_THROUGH) != 0 {
+            return None;
+        }
+        let bank = match addr {
+            0x8000..=0xBFFF if (self.control & BANK_8000) != 0 => 1,
+            0x8000..=0xBFFF => 0,
+            _ if (self.control & BANK_C000) != 0 => 3,
+            _ => 2,
+        };
+        Some(bank * 0x4000 + (addr as usize & 0x3FFF))
+    }
+
+    fn socket(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x9000..=0xAFFF => Some((addr as usize >> 12) - 9),
+            _ => None,
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> Option<u8> {
+        if let Some(index) = self.bank_index(addr) {
+            return Some(self.ram[index]);
+        }
+        let ram = self.socket_ram[self.socket(addr)?].as_ref()?;
+        Some(ram[addr as usize & 0x0FFF])
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) -> bool {
+        if addr == 0xFFF0 {
+            self.control = val;
+            return true;
+        }
+        if let Some(index) = self.bank_index(addr) {
+            let protect = if addr < 0xC000 {
+                PROTECT_8000
+            } else {
+                PROTECT_C000
+            };
+            if (self.control & protect) == 0 {
+                self.ram[index] = val;
+            }
+            return true;
+        }
+        match self.socket(addr).and_then(|s| self.socket_ram[s].as_mut()) {
+            Some(ram) => {
+                ram[addr as usize & 0x0FFF] = val;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bank_switching() {
+        let mut expansion = MemoryExpansion::new([false, false]);
+        assert_eq!(expansion.read(0x8000), None);
+        assert!(!expansion.write(0x9000, 1));
+
+        expansion.write(0xFFF0, ENABLED);
+        expansion.write(0x8000, 0x11);
+        expansion.write(0xC000, 0x22);
+        expansion.write(0xFFF0, ENABLED | BANK_8000 | BANK_C000);
+        assert_eq!(expansion.read(0x8000), Some(0));
+        expansion.write(0x8000, 0x33);
+        expansion.write(0xFFF0, ENABLED);
+        assert_eq!(expansion.read(0x8000), Some(0x11));
+        assert_eq!(expansion.read(0xC000), Some(0x22));
+
+        expansion.write(
+            0xFFF0,
+            ENABLED | PROTECT_8000 | SCREEN_PEEK_THROUGH | IO_PEEK_THROUGH,
+        );
+        expansion.write(0x9000, 0x44);
+        assert_eq!(expansion.read(0x9000), Some(0));
+        assert_eq!(expansion.read(0x8000), None);
+        assert_eq!(expansion.read(0xE810), None);
+        assert_eq!(expansion.read(0xF000), Some(0));
+    }
+
+    #[test]
+    fn test_socket_ram() {
+        let mut expansion = MemoryExpansion::new([true, true]);
+        assert!(expansion.write(0xA123, 0x55));
+        assert_eq!(expansion.read(0xA123), Some(0x55));
+        assert_eq!(expansion.read(0xB000), None);
+
+        expansion.write(0xFFF0, ENABLED);
+        expansion.write(0xA123, 0x66);
+        assert_eq!(expansion.read(0xA123), Some(0x66));
+        expansion.write(0xFFF0, 0);
+        assert_eq!(expansion.read(0xA123), Some(0x55));
+    }
+
+    #[test]
+    fn test_socket_ram_jumpers() {
+        let mut expansion = MemoryExpansion::new([false, true]);
+        assert!(!expansion.write(0x9123, 0x55));
+        assert_eq!(expansion.read(0x9123), None);
+        assert!(expansion.write(0xA123, 0x66));
+        assert_eq!(expansion.read(0xA123), Some(0x66));
+
+        expansion.set_socket_ram([true, false]);
+        assert!(expansion.has_socket_ram(0));
+        assert!(!expansion.has_socket_ram(1));
+        assert!(expansion.write(0x9123, 0x77));
+        assert_eq!(expansion.read(0x9123), Some(0x77));
+        assert_eq!(expansion.read(0xA123), None);
+    }
+}
@@ -0,0 +1,446 @@
+use crate::bus::PetBus;
+use crate::opcodes::{assemble, disassemble};
+use crate::rom_loader::OptionSocket;
+use mos6502::bus::Bus;
+use mos6502::cpu::Cpu;
+
+const MAX_LINES: usize = 200;
+const HELP: &[&str] = &[
+    "M [start [end]]        memory dump",
+    "D [start [end]]        disassemble",
+    "A addr instruction     assemble",
+    "F start end byte       fill",
+    "H start end bytes|'text'  hunt",
+    "C start end dest       compare",
+    "T start end dest       transfer",
+    "R [PC=.. A=.. X=.. Y=.. SP=.. SR=..]  registers",
+    "G [addr]               go",
+    "Z [count]              step",
+    "MAP                    memory map",
+    "X                      exit",
+];
+
+type Step = fn(&mut Cpu<PetBus>) -> u32;
+
+pub struct Monitor {
+    visible: bool,
+    input: String,
+    last_command: String,
+    lines: Vec<String>,
+    next_address: u16,
+}
+
+fn parse_address(text: &str) -> Result<u16, String> {
+    let digits = text.strip_prefix('$').unwrap_or(text);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Bad address: {}", text))
+}
+
+fn parse_byte(text: &str) -> Result<u8, String> {
+    let digits = text.strip_prefix('$').unwrap_or(text);
+    u8::from_str_radix(digits, 16).map_err(|_| format!("Bad byte: {}", text))
+}
+
+fn parse_range(start: Option<&&str>, end: Option<&&str>) -> Result<(u16, u16), String> {
+    let start = parse_address(start.ok_or("Missing start address")?)?;
+    let end = parse_address(end.ok_or("Missing end address")?)?;
+    if end < start {
+        return Err("End address is before start".to_string());
+    }
+    Ok((start, end))
+}
+
+fn peek(cpu: &mut Cpu<PetBus>, addr: u16) -> u8 {
+    cpu.bus.peek(addr).unwrap_or(0xFF)
+}
+
+fn describe_socket(socket: &Option<OptionSocket>) -> String {
+    match socket {
+        Some(OptionSocket::Rom(data)) => format!("option ROM ({} bytes)", data.len()),
+        Some(OptionSocket::Ram(_)) => "RAM".to_string(),
+        None => "empty".to_string(),
+    }
+}
+
+impl Monitor {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            input: String::new(),
+            last_command: String::new(),
+            lines: Vec::new(),
+            next_address: 0,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn show(&mut self, cpu: &Cpu<PetBus>) {
+        self.visible = true;
+        self.next_address = cpu.pc;
+        self.print_registers(cpu);
+    }
+
+    pub fn hide(&mut self) {
+        self.visible = false;
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn type_text(&mut self, text: &str) {
+        self.input.push_str(text);
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    pub fn recall(&mut self) {
+        self.input = self.last_command.clone();
+    }
+
+    pub fn execute(&mut self, cpu: &mut Cpu<PetBus>, step: Step) {
+        let line = std::mem::take(&mut self.input);
+        self.print(format!(". {}", line));
+        if !line.trim().is_empty() {
+            self.last_command = line.clone();
+        }
+        if let Err(e) = self.run(line.trim(), cpu, step) {
+            self.print(format!("? {}", e));
+        }
+    }
+
+    fn print(&mut self, line: String) {
+        self.lines.push(line);
+        if self.lines.len() > MAX_LINES {
+            self.lines.remove(0);
+        }
+    }
+
+    fn run(&mut self, line: &str, cpu: &mut Cpu<PetBus>, step: Step) -> Result<(), String> {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let Some(command) = args.first() else {
+            return Ok(());
+        };
+        match command.to_uppercase().as_str() {
+            "M" => self.memory(&args, cpu),
+            "D" => self.disassemble(&args, cpu),
+            "A" => self.assemble(line, cpu),
+            "F" => {
+                let (start, end) = parse_range(args.get(1), args.get(2))?;
+                let value = parse_byte(args.get(3).ok_or("Missing fill byte")?)?;
+                for addr in start..=end {
+                    cpu.bus.write(addr, value);
+                }
+                Ok(())
+            }
+            "H" => self.hunt(line, cpu),
+            "C" | "T" => {
+                let (start, end) = parse_range(args.get(1), args.get(2))?;
+                let dest = parse_address(args.get(3).ok_or("Missing destination")?)?;
+                if command.eq_ignore_ascii_case("T") {
+                    let data: Vec<u8> = (start..=end).map(|a| peek(cpu, a)).collect();
+                    for (i, byte) in data.into_iter().enumerate() {
+                        cpu.bus.write(dest.wrapping_add(i as u16), byte);
+                    }
+                } else {
+                    let differences: Vec<String> = (start..=end)
+                        .filter(|&a| {
+                            let other = dest.wrapping_add(a - start);
+                            peek(cpu, a) != peek(cpu, other)
+                        })
+                        .map(|a| format!("{:04X}", a))
+                        .collect();
+                    for chunk in differences.chunks(10) {
+                        self.print(chunk.join(" "));
+                    }
+                }
+                Ok(())
+            }
+            "R" => {
+                for assignment in &args[1..] {
+                    let (name, value) = assignment
+                        .split_once('=')
+                        .ok_or(format!("Bad register assignment: {}", assignment))?;
+                    match name.to_uppercase().as_str() {
+                        "PC" => cpu.pc = parse_address(value)?,
+                        "A" | "AC" => cpu.a = parse_byte(value)?,
+                        "X" | "XR" => cpu.x = parse_byte(value)?,
+                        "Y" | "YR" => cpu.y = parse_byte(value)?,
+                        "SP" => cpu.sp = parse_byte(value)?,
+                        "SR" | "P" => cpu.status = parse_byte(value)?,
+                        _ => return Err(format!("Unknown register: {}", name)),
+                    }
+                }
+                self.print_registers(cpu);
+                Ok(())
+            }
+            "G" => {
+                if let Some(addr) = args.get(1) {
+                    cpu.pc = parse_address(addr)?;
+                }
+                self.hide();
+                Ok(())
+            }
+            "Z" => {
+                let count = match args.get(1) {
+                    Some(count) => count.parse().map_err(|_| format!("Bad count: {}", count))?,
+                    None => 1,
+                };
+                for _ in 0..count {
+                    step(cpu);
+                }
+                let (text, _) = disassemble(cpu.pc, |a| peek(cpu, a));
+                self.print(text);
+                self.print_registers(cpu);
+                Ok(())
+            }
+            "MAP" => {
+                self.memory_map(cpu);
+                Ok(())
+            }
+            "X" => {
+                self.hide();
+                Ok(())
+            }
+            "?" | "HELP" => {
+                for line in HELP {
+                    self.print(line.to_string());
+                }
+                Ok(())
+            }
+            _ => Err(format!("Unknown command: {}", command)),
+        }
+    }
+
+    fn start_and_end(&self, args: &[&str], length: u16) -> Result<(u16, Option<u16>), String> {
+        let start = match args.get(1) {
+            Some(addr) => parse_address(addr)?,
+            None => self.next_address,
+        };
+        match args.get(2) {
+            Some(_) => parse_range(args.get(1), args.get(2)).map(|(s, e)| (s, Some(e))),
+            None if length > 0 => Ok((start, Some(start.saturating_add(length - 1)))),
+            None => Ok((start, None)),
+        }
+    }
+
+    fn memory(&mut self, args: &[&str], cpu: &mut Cpu<PetBus>) -> Result<(), String> {
+        let (start, end) = self.start_and_end(args, 0x80)?;
+        let end = end.unwrap_or(start);
+        let mut addr = start as u32;
+        while addr <= end as u32 {
+            let row: Vec<Option<u8>> = (0..8).map(|i| cpu.bus.peek((addr + i) as u16)).collect();
+            let hex: Vec<String> = row
+                .iter()
+                .map(|b| b.map_or("--".to_string(), |b| format!("{:02X}", b)))
+                .collect();
+            let text: String = row
+                .iter()
+                .map(|b| match b {
+                    Some(b @ 0x20..=0x5F) => *b as char,
+                    _ => '.',
+                })
+                .collect();
+            self.print(format!(":{:04X} {}  {}", addr, hex.join(" "), text));
+            addr += 8;
+        }
+        self.next_address = addr as u16;
+        Ok(())
+    }
+
+    fn disassemble(&mut self, args: &[&str], cpu: &mut Cpu<PetBus>) -> Result<(), String> {
+        let (start, end) = self.start_and_end(args, 0)?;
+        let mut addr = start;
+        for count in 0.. {
+            match end {
+                Some(end) if addr > end || addr < start => break,
+                None if count == 16 => break,
+                _ => {}
+            }
+            let (text, len) = disassemble(addr, |a| peek(cpu, a));
+            self.print(text);
+            addr = addr.wrapping_add(len);
+        }
+        self.next_address = addr;
+        Ok(())
+    }
+
+    fn assemble(&mut self, line: &str, cpu: &mut Cpu<PetBus>) -> Result<(), String> {
+        let mut parts = line.splitn(3, ' ').skip(1);
+        let addr = parse_address(parts.next().ok_or("Missing address")?)?;
+        let bytes = assemble(addr, parts.next().ok_or("Missing instruction")?)?;
+        for (i, byte) in bytes.iter().enumerate() {
+            cpu.bus.write(addr.wrapping_add(i as u16), *byte);
+        }
+        let (text, len) = disassemble(addr, |a| peek(cpu, a));
+        self.print(text);
+        self.input = format!("A {:04X} ", addr.wrapping_add(len));
+        Ok(())
+    }
+
+    fn hunt(&mut self, line: &str, cpu: &mut Cpu<PetBus>) -> Result<(), String> {
+        let parts: Vec<&str> = line.splitn(4, ' ').collect();
+        let (start, end) = parse_range(parts.get(1), parts.get(2))?;
+        let pattern = parts.get(3).ok_or("Missing search bytes")?.trim();
+        let needle: Vec<u8> = match pattern.strip_prefix(['\'', '"']) {
+            Some(text) => text
+                .trim_end_matches(['\'', '"'])
+                .to_uppercase()
+                .bytes()
+                .collect(),
+            None => pattern
+                .split_whitespace()
+                .map(parse_byte)
+                .collect::<Result<_, _>>()?,
+        };
+        if needle.is_empty() {
+            return Err("Missing search bytes".to_string());
+        }
+        let haystack: Vec<u8> = (start..=end).map(|a| peek(cpu, a)).collect();
+        let found: Vec<String> = haystack
+            .windows(needle.len())
+            .enumerate()
+            .filter(|(_, window)| *window == needle.as_slice())
+            .map(|(i, _)| format!("{:04X}", start as usize + i))
+            .collect();
+        for chunk in found.chunks(10) {
+            self.print(chunk.join(" "));
+        }
+        Ok(())
+    }
+
+    fn print_registers(&mut self, cpu: &Cpu<PetBus>) {
+        self.print("   PC  SR AC XR YR SP  NV-BDIZC".to_string());
+        self.print(format!(
+            ";  {:04X} {:02X} {:02X} {:02X} {:02X} {:02X}  {:08b}",
+            cpu.pc, cpu.status, cpu.a, cpu.x, cpu.y, cpu.sp, cpu.status
+        ));
+        if let Some(expansion) = &cpu.bus.expansion {
+            self.print(format!(
+                "   $FFF0 expansion control {:02X}",
+                expansion.control
+            ));
+        }
+    }
+
+    fn memory_map(&mut self, cpu: &Cpu<PetBus>) {
+        let bus = &cpu.bus;
+        let mut map = vec![format!("0000-{:04X} RAM", bus.ram_size - 1)];
+        if bus.ram_size < 0x8000 {
+            map.push(format!("{:04X}-7FFF open bus", bus.ram_size));
+        }
+        map.push(format!(
+            "8000-8FFF video RAM ({} bytes, mirrored)",
+            bus.model.video_ram_size()
+        ));
+        for (socket, base) in ["9000-9FFF", "A000-AFFF"].into_iter().enumerate() {
+            let jumpered = bus
+                .expansion
+                .as_ref()
+                .is_some_and(|e| e.has_socket_ram(socket));
+            if jumpered {
+                map.push(format!("{} socket RAM (8296 jumper)", base));
+            } else {
+                map.push(format!(
+                    "{} {}",
+                    base,
+                    describe_socket(&bus.option_sockets[socket])
+                ));
+            }
+        }
+        if bus.roms.basic_b000.is_some() {
+            map.push("B000-DFFF BASIC ROM".to_string());
+        } else {
+            map.push("B000-BFFF empty".to_string());
+            map.push("C000-DFFF BASIC ROM".to_string());
+        }
+        map.push("E000-E7FF editor ROM".to_string());
+        map.push("E810-E813 PIA 1 (keyboard, cassette)".to_string());
+        map.push("E820-E823 PIA 2 (IEEE-488)".to_string());
+        map.push("E840-E84F VIA".to_string());
+        if bus.model.has_crtc() {
+            map.push("E880-E881 CRTC".to_string());
+        }
+        map.push("F000-FFFF KERNAL ROM".to_string());
+        if let Some(expansion) = &bus.expansion {
+            map.push(format!(
+                "8000-FFFF 64K expansion, control {:02X} ({})",
+                expansion.control,
+                if (expansion.control & 0x80) != 0 {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            ));
+        }
+        for line in map {
+            self.print(line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::Model;
+
+    fn no_step(_cpu: &mut Cpu<PetBus>) -> u32 {
+        0
+    }
+
+    fn run(monitor: &mut Monitor, cpu: &mut Cpu<PetBus>, command: &str) -> Vec<String> {
+        let before = monitor.lines().len();
+        monitor.input.clear();
+        monitor.type_text(command);
+        monitor.execute(cpu, no_step);
+        monitor.lines()[before + 1..].to_vec()
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut cpu = Cpu::new(PetBus::with_blank_roms(Model::Pet4032));
+        let mut monitor = Monitor::new();
+
+        run(&mut monitor, &mut cpu, "F 1000 100F AA");
+        run(&mut monitor, &mut cpu, "T 1000 1007 2000");
+        assert_eq!(cpu.bus.read(0x2007), 0xAA);
+        assert_eq!(
+            run(&mut monitor, &mut cpu, "C 1000 100F 2000"),
+            ["1008 1009 100A 100B 100C 100D 100E 100F"]
+        );
+        assert_eq!(
+            run(
+                &mut monitor,
+                &mut cpu,
+                "H 0000 7FFF AA AA AA AA AA AA AA AA AA"
+            ),
+            ["1000 1001 1002 1003 1004 1005 1006 1007"]
+        );
+
+        assert_eq!(
+            run(&mut monitor, &mut cpu, "A 3000 LDA #$41"),
+            ["3000  A9 41     LDA #$41"]
+        );
+        assert_eq!(monitor.input(), "A 3002 ");
+        monitor.type_text("STA $8000");
+        monitor.execute(&mut cpu, no_step);
+        assert_eq!(
+            run(&mut monitor, &mut cpu, "M 3000 3007"),
+            [":3000 A9 41 8D 00 80 00 00 00  .A......"]
+        );
+
+        let registers = run(&mut monitor, &mut cpu, "R PC=3000 A=12");
+        assert_eq!(cpu.pc, 0x3000);
+        assert_eq!(cpu.a, 0x12);
+        assert!(registers[1].starts_with(";  3000"));
+        assert!(run(&mut monitor, &mut cpu, "Q")[0].starts_with("?"));
+    }
+}
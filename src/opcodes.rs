@@ -0,0 +1,256 @@
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+impl Mode {
+    pub fn operand_len(self) -> u16 {
+        match self {
+            Mode::Implied | Mode::Accumulator => 0,
+            Mode::Absolute | Mode::AbsoluteX | Mode::AbsoluteY | Mode::Indirect => 2,
+            _ => 1,
+        }
+    }
+}
+
+use Mode::*;
+
+#[rustfmt::skip]
+const OPCODES: &[(u8, &str, Mode)] = &[
+    (0x69, "ADC", Immediate), (0x65, "ADC", ZeroPage), (0x75, "ADC", ZeroPageX),
+    (0x6D, "ADC", Absolute), (0x7D, "ADC", AbsoluteX), (0x79, "ADC", AbsoluteY),
+    (0x61, "ADC", IndirectX), (0x71, "ADC", IndirectY),
+    (0x29, "AND", Immediate), (0x25, "AND", ZeroPage), (0x35, "AND", ZeroPageX),
+    (0x2D, "AND", Absolute), (0x3D, "AND", AbsoluteX), (0x39, "AND", AbsoluteY),
+    (0x21, "AND", IndirectX), (0x31, "AND", IndirectY),
+    (0x0A, "ASL", Accumulator), (0x06, "ASL", ZeroPage), (0x16, "ASL", ZeroPageX),
+    (0x0E, "ASL", Absolute), (0x1E, "ASL", AbsoluteX),
+    (0x90, "BCC", Relative), (0xB0, "BCS", Relative), (0xF0, "BEQ", Relative),
+    (0x30, "BMI", Relative), (0xD0, "BNE", Relative), (0x10, "BPL", Relative),
+    (0x50, "BVC", Relative), (0x70, "BVS", Relative),
+    (0x24, "BIT", ZeroPage), (0x2C, "BIT", Absolute),
+    (0x00, "BRK", Implied),
+    (0x18, "CLC", Implied), (0xD8, "CLD", Implied), (0x58, "CLI", Implied), (0xB8, "CLV", Implied),
+    (0xC9, "CMP", Immediate), (0xC5, "CMP", ZeroPage), (0xD5, "CMP", ZeroPageX),
+    (0xCD, "CMP", Absolute), (0xDD, "CMP", AbsoluteX), (0xD9, "CMP", AbsoluteY),
+    (0xC1, "CMP", IndirectX), (0xD1, "CMP", IndirectY),
+    (0xE0, "CPX", Immediate), (0xE4, "CPX", ZeroPage), (0xEC, "CPX", Absolute),
+    (0xC0, "CPY", Immediate), (0xC4, "CPY", ZeroPage), (0xCC, "CPY", Absolute),
+    (0xC6, "DEC", ZeroPage), (0xD6, "DEC", ZeroPageX), (0xCE, "DEC", Absolute), (0xDE, "DEC", AbsoluteX),
+    (0xCA, "DEX", Implied), (0x88, "DEY", Implied),
+    (0x49, "EOR", Immediate), (0x45, "EOR", ZeroPage), (0x55, "EOR", ZeroPageX),
+    (0x4D, "EOR", Absolute), (0x5D, "EOR", AbsoluteX), (0x59, "EOR", AbsoluteY),
+    (0x41, "EOR", IndirectX), (0x51, "EOR", IndirectY),
+    (0xE6, "INC", ZeroPage), (0xF6, "INC", ZeroPageX), (0xEE, "INC", Absolute), (0xFE, "INC", AbsoluteX),
+    (0xE8, "INX", Implied), (0xC8, "INY", Implied),
+    (0x4C, "JMP", Absolute), (0x6C, "JMP", Indirect),
+    (0x20, "JSR", Absolute),
+    (0xA9, "LDA", Immediate), (0xA5, "LDA", ZeroPage), (0xB5, "LDA", ZeroPageX),
+    (0xAD, "LDA", Absolute), (0xBD, "LDA", AbsoluteX), (0xB9, "LDA", AbsoluteY),
+    (0xA1, "LDA", IndirectX), (0xB1, "LDA", IndirectY),
+    (0xA2, "LDX", Immediate), (0xA6, "LDX", ZeroPage), (0xB6, "LDX", ZeroPageY),
+    (0xAE, "LDX", Absolute), (0xBE, "LDX", AbsoluteY),
+    (0xA0, "LDY", Immediate), (0xA4, "LDY", ZeroPage), (0xB4, "LDY", ZeroPageX),
+    (0xAC, "LDY", Absolute), (0xBC, "LDY", AbsoluteX),
+    (0x4A, "LSR", Accumulator), (0x46, "LSR", ZeroPage), (0x56, "LSR", ZeroPageX),
+    (0x4E, "LSR", Absolute), (0x5E, "LSR", AbsoluteX),
+    (0xEA, "NOP", Implied),
+    (0x09, "ORA", Immediate), (0x05, "ORA", ZeroPage), (0x15, "ORA", ZeroPageX),
+    (0x0D, "ORA", Absolute), (0x1D, "ORA", AbsoluteX), (0x19, "ORA", AbsoluteY),
+    (0x01, "ORA", IndirectX), (0x11, "ORA", IndirectY),
+    (0x48, "PHA", Implied), (0x08, "PHP", Implied), (0x68, "PLA", Implied), (0x28, "PLP", Implied),
+    (0x2A, "ROL", Accumulator), (0x26, "ROL", ZeroPage), (0x36, "ROL", ZeroPageX),
+    (0x2E, "ROL", Absolute), (0x3E, "ROL", AbsoluteX),
+    (0x6A, "ROR", Accumulator), (0x66, "ROR", ZeroPage), (0x76, "ROR", ZeroPageX),
+    (0x6E, "ROR", Absolute), (0x7E, "ROR", AbsoluteX),
+    (0x40, "RTI", Implied), (0x60, "RTS", Implied),
+    (0xE9, "SBC", Immediate), (0xE5, "SBC", ZeroPage), (0xF5, "SBC", ZeroPageX),
+    (0xED, "SBC", Absolute), (0xFD, "SBC", AbsoluteX), (0xF9, "SBC", AbsoluteY),
+    (0xE1, "SBC", IndirectX), (0xF1, "SBC", IndirectY),
+    (0x38, "SEC", Implied), (0xF8, "SED", Implied), (0x78, "SEI", Implied),
+    (0x85, "STA", ZeroPage), (0x95, "STA", ZeroPageX), (0x8D, "STA", Absolute),
+    (0x9D, "STA", AbsoluteX), (0x99, "STA", AbsoluteY), (0x81, "STA", IndirectX), (0x91, "STA", IndirectY),
+    (0x86, "STX", ZeroPage), (0x96, "STX", ZeroPageY), (0x8E, "STX", Absolute),
+    (0x84, "STY", ZeroPage), (0x94, "STY", ZeroPageX), (0x8C, "STY", Absolute),
+    (0xAA, "TAX", Implied), (0xA8, "TAY", Implied), (0xBA, "TSX", Implied),
+    (0x8A, "TXA", Implied), (0x9A, "TXS", Implied), (0x98, "TYA", Implied),
+];
+
+fn lookup(opcode: u8) -> Option<(&'static str, Mode)> {
+    OPCODES
+        .iter()
+        .find(|(code, _, _)| *code == opcode)
+        .map(|&(_, mnemonic, mode)| (mnemonic, mode))
+}
+
+fn encode(mnemonic: &str, mode: Mode) -> Option<u8> {
+    OPCODES
+        .iter()
+        .find(|(_, name, m)| *name == mnemonic && *m == mode)
+        .map(|&(code, _, _)| code)
+}
+
+pub fn disassemble(addr: u16, mut read: impl FnMut(u16) -> u8) -> (String, u16) {
+    let opcode = read(addr);
+    let Some((mnemonic, mode)) = lookup(opcode) else {
+        return (format!("{:04X}  {:02X}        ???", addr, opcode), 1);
+    };
+    let len = 1 + mode.operand_len();
+    let bytes: Vec<u8> = (0..len).map(|i| read(addr.wrapping_add(i))).collect();
+    let byte = bytes.get(1).copied().unwrap_or(0);
+    let word = u16::from_le_bytes([byte, bytes.get(2).copied().unwrap_or(0)]);
+    let operand = match mode {
+        Implied => String::new(),
+        Accumulator => " A".to_string(),
+        Immediate => format!(" #${:02X}", byte),
+        ZeroPage => format!(" ${:02X}", byte),
+        ZeroPageX => format!(" ${:02X},X", byte),
+        ZeroPageY => format!(" ${:02X},Y", byte),
+        Absolute => format!(" ${:04X}", word),
+        AbsoluteX => format!(" ${:04X},X", word),
+        AbsoluteY => format!(" ${:04X},Y", word),
+        Indirect => format!(" (${:04X})", word),
+        IndirectX => format!(" (${:02X},X)", byte),
+        IndirectY => format!(" (${:02X}),Y", byte),
+        Relative => format!(
+            " ${:04X}",
+            addr.wrapping_add(2).wrapping_add(byte as i8 as u16)
+        ),
+    };
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    (
+        format!(
+            "{:04X}  {:<8}  {}{}",
+            addr,
+            hex.join(" "),
+            mnemonic,
+            operand
+        ),
+        len,
+    )
+}
+
+fn parse_value(text: &str) -> Result<(u16, bool), String> {
+    let digits = text.strip_prefix('$').unwrap_or(text);
+    let value = u16::from_str_radix(digits, 16).map_err(|_| format!("Bad number: {}", text))?;
+    Ok((value, digits.len() <= 2))
+}
+
+pub fn assemble(addr: u16, text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim().to_uppercase();
+    let (mnemonic, operand) = text.split_once(' ').unwrap_or((&text, ""));
+    let operand: String = operand.chars().filter(|c| !c.is_whitespace()).collect();
+    if !OPCODES.iter().any(|(_, name, _)| *name == mnemonic) {
+        return Err(format!("Unknown instruction: {}", mnemonic));
+    }
+
+    let candidates: Vec<(Mode, u16)> = if operand.is_empty() || operand == "A" {
+        vec![(Implied, 0), (Accumulator, 0)]
+    } else if let Some(value) = operand.strip_prefix('#') {
+        let (value, _) = parse_value(value)?;
+        if value > 0xFF {
+            return Err(format!("Immediate value too large: {}", operand));
+        }
+        vec![(Immediate, value)]
+    } else if let Some(inner) = operand
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix(",X)"))
+    {
+        vec![(IndirectX, parse_value(inner)?.0)]
+    } else if let Some(inner) = operand
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix("),Y"))
+    {
+        vec![(IndirectY, parse_value(inner)?.0)]
+    } else if let Some(inner) = operand.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        vec![(Indirect, parse_value(inner)?.0)]
+    } else if let Some(value) = operand.strip_suffix(",X") {
+        let (value, short) = parse_value(value)?;
+        [(ZeroPageX, short), (AbsoluteX, true)]
+            .into_iter()
+            .filter(|&(_, allowed)| allowed)
+            .map(|(mode, _)| (mode, value))
+            .collect()
+    } else if let Some(value) = operand.strip_suffix(",Y") {
+        let (value, short) = parse_value(value)?;
+        [(ZeroPageY, short), (AbsoluteY, true)]
+            .into_iter()
+            .filter(|&(_, allowed)| allowed)
+            .map(|(mode, _)| (mode, value))
+            .collect()
+    } else {
+        let (value, short) = parse_value(&operand)?;
+        [(Relative, true), (ZeroPage, short), (Absolute, true)]
+            .into_iter()
+            .filter(|&(_, allowed)| allowed)
+            .map(|(mode, _)| (mode, value))
+            .collect()
+    };
+
+    let (opcode, mode, value) = candidates
+        .into_iter()
+        .find_map(|(mode, value)| encode(mnemonic, mode).map(|code| (code, mode, value)))
+        .ok_or_else(|| format!("Bad addressing mode: {}", text))?;
+    match mode.operand_len() {
+        0 => Ok(vec![opcode]),
+        1 if mode == Relative => {
+            let offset = value.wrapping_sub(addr.wrapping_add(2)) as i16;
+            if !(-128..=127).contains(&offset) {
+                return Err(format!("Branch out of range: {}", text));
+            }
+            Ok(vec![opcode, offset as u8])
+        }
+        1 if value > 0xFF => Err(format!("Operand too large: {}", text)),
+        1 => Ok(vec![opcode, value as u8]),
+        _ => Ok(vec![opcode, value as u8, (value >> 8) as u8]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble_round_trip() {
+        let cases = [
+            ("LDA #$12", vec![0xA9, 0x12]),
+            ("lda 12", vec![0xA5, 0x12]),
+            ("LDA $0012", vec![0xAD, 0x12, 0x00]),
+            ("STA ($FB),Y", vec![0x91, 0xFB]),
+            ("LDX $10,Y", vec![0xB6, 0x10]),
+            ("JMP ($FFFC)", vec![0x6C, 0xFC, 0xFF]),
+            ("ASL", vec![0x0A]),
+            ("BNE $0FF0", vec![0xD0, 0xEE]),
+            ("RTS", vec![0x60]),
+        ];
+        for (text, bytes) in cases {
+            assert_eq!(assemble(0x1000, text).unwrap(), bytes, "{}", text);
+        }
+        assert!(assemble(0x1000, "BNE $2000").is_err());
+        assert!(assemble(0x1000, "STA #$12").is_err());
+        assert!(assemble(0x1000, "XYZ").is_err());
+
+        let memory = [0x91, 0xFB, 0xD0, 0xFE];
+        let read = |addr: u16| memory[(addr - 0x1000) as usize];
+        assert_eq!(
+            disassemble(0x1000, read),
+            ("1000  91 FB     STA ($FB),Y".to_string(), 2)
+        );
+        assert_eq!(
+            disassemble(0x1002, read),
+            ("1002  D0 FE     BNE $1002".to_string(), 2)
+        );
+    }
+}
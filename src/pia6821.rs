@@ -5,12 +5,14 @@ pub struct Pia6821 {
     ddrb: u8,
     orb: u8,
     crb: u8,
+    port_a_input: u8,
+    ca1: bool,
+    cb1: bool,
     keyboard_matrix: [[bool; 8]; 10],
     irqa1: bool,
     irqa2: bool,
     irqb1: bool,
     irqb2: bool,
-    cycle_count: u32,
     auto_type_queue: Vec<(usize, usize)>,
     auto_type_delay: u32,
     auto_type_timer: u32,
@@ -33,12 +35,14 @@ impl Pia6821 {
             ddrb: 0,
             orb: 0,
             crb: 0,
+            port_a_input: 0xF0,
+            ca1: true,
+            cb1: true,
             keyboard_matrix: [[false; 8]; 10],
             irqa1: false,
             irqa2: false,
             irqb1: false,
             irqb2: false,
-            cycle_count: 0,
             auto_type_queue: Vec::new(),
             auto_type_delay: 50000,
             auto_type_timer: 0,
@@ -47,13 +51,6 @@ impl Pia6821 {
     }
 
     pub fn tick(&mut self, cycles: u32) {
-        self.cycle_count += cycles;
-
-        if self.cycle_count >= 16666 {
-            self.cycle_count -= 16666;
-            self.irqb1 = true;
-        }
-
         if !self.auto_type_queue.is_empty() {
             self.auto_type_timer += cycles;
             if self.auto_type_timer >= self.auto_type_delay {
@@ -96,8 +93,9 @@ impl Pia6821 {
         match reg {
             0 => {
                 if (self.cra & 0x04) != 0 {
-                    let external_input: u8 = 0xF0;
-                    (self.ora & self.ddra) | (external_input & !self.ddra)
+                    self.irqa1 = false;
+                    self.irqa2 = false;
+                    (self.ora & self.ddra) | (self.port_a_input & !self.ddra)
                 } else {
                     self.ddra
                 }
@@ -171,6 +169,44 @@ impl Pia6821 {
         ca1_irq || cb1_irq
     }
 
+    pub fn set_port_a_input(&mut self, val: u8) {
+        self.port_a_input = val;
+    }
+
+    pub fn port_b_output(&self) -> u8 {
+        (self.orb & self.ddrb) | !self.ddrb
+    }
+
+    pub fn ca2_output(&self) -> bool {
+        Self::c2_output(self.cra)
+    }
+
+    pub fn cb2_output(&self) -> bool {
+        Self::c2_output(self.crb)
+    }
+
+    fn c2_output(control: u8) -> bool {
+        if (control & 0x30) == 0x30 {
+            (control & 0x08) != 0
+        } else {
+            true
+        }
+    }
+
+    pub fn set_ca1(&mut self, level: bool) {
+        if level != self.ca1 && level == ((self.cra & 0x02) != 0) {
+            self.irqa1 = true;
+        }
+        self.ca1 = level;
+    }
+
+    pub fn set_cb1(&mut self, level: bool) {
+        if level != self.cb1 && level == ((self.crb & 0x02) != 0) {
+            self.irqb1 = true;
+        }
+        self.cb1 = level;
+    }
+
     fn scan_keyboard(&self) -> u8 {
         let mut columns = 0xFF_u8;
         let row = (self.ora & 0x0F) as usize;
@@ -192,3 +228,25 @@ impl Pia6821 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_a_read_clears_ca1_interrupt() {
+        let mut pia = Pia6821::new();
+        pia.write_register(1, 0x01);
+        pia.set_ca1(false);
+        assert!(pia.irq_out());
+        pia.read_register(0);
+        assert!(pia.irq_out());
+
+        pia.write_register(1, 0x05);
+        pia.set_ca1(true);
+        pia.set_ca1(false);
+        assert!(pia.irq_out());
+        pia.read_register(0);
+        assert!(!pia.irq_out());
+    }
+}
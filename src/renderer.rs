@@ -1,50 +1,120 @@
 use crate::bus::PetBus;
+use crate::config::Display;
 use crate::file_dialog::FileDialog;
+use crate::monitor::Monitor;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 
-pub fn draw_pet_screen(canvas: &mut Canvas<Window>, bus: &PetBus) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
+const MIN_WIDTH: u32 = 320;
+const MIN_HEIGHT: u32 = 200;
 
-    let green = Color::RGB(50, 255, 50);
-    canvas.set_draw_color(green);
+pub struct WindowSize {
+    pending: Option<(u32, u32)>,
+}
 
-    let mut video_ram_start = bus.crtc.screen_start_address() as usize;
+impl WindowSize {
+    pub fn new() -> Self {
+        Self { pending: None }
+    }
 
-    if video_ram_start >= 0x800 {
-        video_ram_start = 0_usize + (video_ram_start - 0_usize) % (0x7FF - 0x000 + 1);
+    fn update(&mut self, canvas: &mut Canvas<Window>, bus: &PetBus, display: &Display) {
+        let columns = bus.crtc.displayed_columns();
+        let rows = bus.crtc.displayed_rows();
+        if columns == 0 || rows == 0 {
+            self.pending = None;
+            return;
+        }
+        let pixel_width = 2 * display.scale / bus.crtc.chars_per_clock();
+        let pixel_height = 2 * display.scale;
+        let width = (columns * 8 * pixel_width) as u32;
+        let height = (rows * bus.crtc.scanlines_per_row() * pixel_height) as u32;
+        let size = (
+            width.max(MIN_WIDTH * display.scale as u32),
+            height.max(MIN_HEIGHT * display.scale as u32),
+        );
+
+        if canvas.window().size() == size {
+            self.pending = None;
+        } else if self.pending == Some(size) {
+            let _ = canvas.window_mut().set_size(size.0, size.1);
+            self.pending = None;
+        } else {
+            self.pending = Some(size);
+        }
     }
+}
+
+pub fn draw_pet_screen(
+    canvas: &mut Canvas<Window>,
+    bus: &PetBus,
+    display: &Display,
+    window_size: &mut WindowSize,
+) {
+    window_size.update(canvas, bus, display);
+
+    let columns = bus.crtc.displayed_columns();
+    let rows = bus.crtc.displayed_rows();
+    let scanlines = bus.crtc.scanlines_per_row();
+    let pixel_width = 2 * display.scale / bus.crtc.chars_per_clock();
+    let pixel_height = 2 * display.scale;
+    let video_ram_mask = bus.model.video_ram_size() - 1;
+
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
 
-    let video_ram = &bus.ram[0x8000 + video_ram_start..0x8000 + video_ram_start + 1000];
+    let (r, g, b) = display.color;
+    canvas.set_draw_color(Color::RGB(r, g, b));
+
+    let screen_start = bus.crtc.screen_start_address as usize * bus.crtc.chars_per_clock();
     let char_rom = &bus.roms.char_rom;
+    let charset_offset = if bus.via.control_lines().ca2 {
+        0x400
+    } else {
+        0
+    };
+    let cursor_offset = if bus.crtc.cursor_visible() {
+        Some(
+            bus.crtc
+                .cursor_address
+                .wrapping_sub(bus.crtc.screen_start_address) as usize,
+        )
+    } else {
+        None
+    };
+    let (cursor_start, cursor_end) = bus.crtc.cursor_scanlines();
 
-    for row in 0..25 {
-        for col in 0..40 {
-            let char_code = video_ram[row * 40 + col];
+    for row in 0..rows {
+        for col in 0..columns {
+            let offset = row * columns + col;
+            let char_code = bus.ram[0x8000 + ((screen_start + offset) & video_ram_mask)];
+            let is_cursor = cursor_offset == Some(offset / bus.crtc.chars_per_clock());
             let is_inverted = (char_code & 0x80) != 0;
-            let glyph_offset = ((char_code & 0x7F) as usize) * 8;
-
-            for y in 0..8 {
-                if glyph_offset + y >= char_rom.len() {
-                    continue;
-                }
+            let glyph_offset = charset_offset + ((char_code & 0x7F) as usize) * 8;
 
-                let mut byte = char_rom[glyph_offset + y];
+            for y in 0..scanlines {
+                let mut byte = if y < 8 {
+                    char_rom.get(glyph_offset + y).copied().unwrap_or(0)
+                } else {
+                    0
+                };
 
                 if is_inverted {
                     byte = !byte;
                 }
 
+                if is_cursor && (cursor_start..=cursor_end).contains(&y) {
+                    byte = !byte;
+                }
+
                 for x in 0..8 {
                     if (byte & (0x80 >> x)) != 0 {
                         let _ = canvas.fill_rect(Rect::new(
-                            (col * 8 + x) as i32 * 2,
-                            (row * 8 + y) as i32 * 2,
-                            2,
-                            2,
+                            ((col * 8 + x) * pixel_width) as i32,
+                            ((row * scanlines + y) * pixel_height) as i32,
+                            pixel_width as u32,
+                            pixel_height as u32,
                         ));
                     }
                 }
@@ -73,15 +143,35 @@ fn get_font_path() -> Option<&'static str> {
     None
 }
 
+fn get_mono_font_path() -> Option<&'static str> {
+    [
+        "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+        "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+        "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
+        "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+        "/usr/share/fonts/TTF/LiberationMono-Regular.ttf",
+        "/usr/share/fonts/noto/NotoSansMono-Regular.ttf",
+    ]
+    .into_iter()
+    .find(|path| std::path::Path::new(path).exists())
+}
+
 pub fn draw_file_dialog(
     canvas: &mut Canvas<Window>,
     file_dialog: &FileDialog,
     ttf_context: &sdl2::ttf::Sdl2TtfContext,
 ) {
+    let (width, height) = canvas.output_size().unwrap_or((640, 400));
+    let frame = Rect::new(
+        width as i32 / 16,
+        height as i32 / 10,
+        width - width / 8,
+        height - height / 5,
+    );
     canvas.set_draw_color(Color::RGB(32, 32, 32));
-    canvas.fill_rect(Rect::new(40, 40, 560, 320)).unwrap();
+    canvas.fill_rect(frame).unwrap();
     canvas.set_draw_color(Color::RGB(200, 200, 200));
-    canvas.draw_rect(Rect::new(40, 40, 560, 320)).unwrap();
+    canvas.draw_rect(frame).unwrap();
 
     let font_path = match get_font_path() {
         Some(path) => path,
@@ -105,11 +195,19 @@ pub fn draw_file_dialog(
         .copy(
             &dir_texture,
             None,
-            Rect::new(50, 50, dir_query.width, dir_query.height),
+            Rect::new(
+                frame.x() + 10,
+                frame.y() + 10,
+                dir_query.width,
+                dir_query.height,
+            ),
         )
         .unwrap();
 
-    for (i, entry) in file_dialog.entries().iter().enumerate() {
+    let labels = file_dialog.labels();
+    let rows = ((frame.height() as usize).saturating_sub(60) / 20).max(1);
+    let first = (file_dialog.selected_index() + 1).saturating_sub(rows);
+    for (i, entry) in labels.iter().enumerate().skip(first).take(rows) {
         let color = if i == file_dialog.selected_index() {
             yellow
         } else {
@@ -124,7 +222,12 @@ pub fn draw_file_dialog(
             .copy(
                 &texture,
                 None,
-                Rect::new(50, 70 + (i as i32 * 20), query.width, query.height),
+                Rect::new(
+                    frame.x() + 10,
+                    frame.y() + 30 + ((i - first) as i32 * 20),
+                    query.width,
+                    query.height,
+                ),
             )
             .unwrap();
     }
@@ -139,9 +242,63 @@ pub fn draw_file_dialog(
         .copy(
             &help_texture,
             None,
-            Rect::new(50, 340, help_query.width, help_query.height),
+            Rect::new(
+                frame.x() + 10,
+                frame.bottom() - 20,
+                help_query.width,
+                help_query.height,
+            ),
         )
         .unwrap();
 
     canvas.present();
 }
+
+pub fn draw_monitor(
+    canvas: &mut Canvas<Window>,
+    monitor: &Monitor,
+    ttf_context: &sdl2::ttf::Sdl2TtfContext,
+) {
+    canvas.set_draw_color(Color::RGB(0, 0, 48));
+    canvas.clear();
+
+    let font_path = match get_mono_font_path().or_else(get_font_path) {
+        Some(path) => path,
+        None => {
+            canvas.present();
+            return;
+        }
+    };
+
+    let font = ttf_context.load_font(font_path, 14).unwrap();
+    let white = Color::RGB(255, 255, 255);
+    let texture_creator = canvas.texture_creator();
+    let line_height = font.recommended_line_spacing().max(1);
+    let (_, height) = canvas.window().size();
+    let rows = ((height as i32 - 20) / line_height).max(1) as usize;
+
+    let prompt = format!("{}_", monitor.input());
+    let start = (monitor.lines().len() + 1).saturating_sub(rows);
+    let lines = monitor.lines()[start..]
+        .iter()
+        .chain(std::iter::once(&prompt));
+    for (i, line) in lines.enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let surface = font.render(line).blended(white).unwrap();
+        let texture = texture_creator
+            .create_texture_from_surface(&surface)
+            .unwrap();
+        let query = texture.query();
+        canvas
+            .copy(
+                &texture,
+                None,
+                Rect::new(10, 10 + i as i32 * line_height, query.width, query.height),
+            )
+            .unwrap();
+    }
+
+    canvas.present();
+}
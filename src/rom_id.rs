@@ -0,0 +1,228 @@
+use sha1::{Digest, Sha1};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RomSlot {
+    Basic,
+    Kernal,
+    Editor,
+    Characters,
+}
+
+impl RomSlot {
+    pub fn name(self) -> &'static str {
+        match self {
+            RomSlot::Basic => "BASIC",
+            RomSlot::Kernal => "KERNAL",
+            RomSlot::Editor => "editor",
+            RomSlot::Characters => "character",
+        }
+    }
+
+    pub fn sizes(self) -> &'static [usize] {
+        match self {
+            RomSlot::Basic | RomSlot::Kernal => &[0x1000],
+            RomSlot::Editor | RomSlot::Characters => &[0x800, 0x1000],
+        }
+    }
+}
+
+pub struct KnownRom {
+    pub name: &'static str,
+    pub slot: RomSlot,
+    pub address: Option<u16>,
+    pub crc32: u32,
+    pub sha1: &'static str,
+}
+
+// CRC32 and SHA1 values as listed in MAME's PET driver
+// (src/mame/commodore/pet.cpp). The entries for the images in roms/ were
+// checked against those files. BASIC 1 uses the 901439 numbers of the
+// 6540 chips, which the rom_set file names also follow.
+const KNOWN_ROMS: &[KnownRom] = &[
+    KnownRom {
+        name: "BASIC 1 $C000 (901439-01)",
+        slot: RomSlot::Basic,
+        address: Some(0xC000),
+        crc32: 0xA055E33A,
+        sha1: "831db40324113ee996c434d38b4add3fd1f820bd",
+    },
+    KnownRom {
+        name: "BASIC 1 $C000, revised (901439-09)",
+        slot: RomSlot::Basic,
+        address: Some(0xC000),
+        crc32: 0x03CF16D0,
+        sha1: "1330580c0614d3556a389da4649488ba04a60908",
+    },
+    KnownRom {
+        name: "BASIC 1 $C800 (901439-05)",
+        slot: RomSlot::Basic,
+        address: Some(0xC800),
+        crc32: 0x69FD8A8F,
+        sha1: "70c0f4fa67a70995b168668c957c3fcf2c8641bd",
+    },
+    KnownRom {
+        name: "BASIC 1 $D000 (901439-02)",
+        slot: RomSlot::Basic,
+        address: Some(0xD000),
+        crc32: 0xD349F2D4,
+        sha1: "4bf2c20c51a63d213886957485ebef336bb803d0",
+    },
+    KnownRom {
+        name: "BASIC 1 $D800 (901439-06)",
+        slot: RomSlot::Basic,
+        address: Some(0xD800),
+        crc32: 0x850544EB,
+        sha1: "d293972d529023d8fd1f493149e4777b5c253a69",
+    },
+    KnownRom {
+        name: "Editor 1, 40 columns, graphics keyboard (901439-03)",
+        slot: RomSlot::Editor,
+        address: Some(0xE000),
+        crc32: 0x9E1C5CEA,
+        sha1: "f02f5fb492ba93dbbd390f24c10f7a832dec432a",
+    },
+    KnownRom {
+        name: "KERNAL 1 $F000 (901439-04)",
+        slot: RomSlot::Kernal,
+        address: Some(0xF000),
+        crc32: 0x661A814A,
+        sha1: "960717282878e7de893d87242ddf9d1512be162e",
+    },
+    KnownRom {
+        name: "KERNAL 1 $F800 (901439-07)",
+        slot: RomSlot::Kernal,
+        address: Some(0xF800),
+        crc32: 0xC4F47AD1,
+        sha1: "d440f2510bc52e20c3d6bc8b9ded9cea7f462a9c",
+    },
+    KnownRom {
+        name: "BASIC 2 $C000 (901465-01)",
+        slot: RomSlot::Basic,
+        address: Some(0xC000),
+        crc32: 0x63A7FE4A,
+        sha1: "3622111f486d0e137022523657394befa92bde44",
+    },
+    KnownRom {
+        name: "BASIC 2 $D000 (901465-02)",
+        slot: RomSlot::Basic,
+        address: Some(0xD000),
+        crc32: 0xAE4CB035,
+        sha1: "1bc0ebf27c9bb62ad71bca40313e874234cab6ac",
+    },
+    KnownRom {
+        name: "KERNAL 2 (901465-03)",
+        slot: RomSlot::Kernal,
+        address: Some(0xF000),
+        crc32: 0xF02238E2,
+        sha1: "38742bdf449f629bcba6276ef24d3daeb7da6e84",
+    },
+    KnownRom {
+        name: "Editor 2, 40 columns, graphics keyboard (901447-24)",
+        slot: RomSlot::Editor,
+        address: Some(0xE000),
+        crc32: 0xE459AB32,
+        sha1: "5e5502ce32f5a7e387d65efe058916282041e54b",
+    },
+    KnownRom {
+        name: "BASIC 4 $B000 (901465-19)",
+        slot: RomSlot::Basic,
+        address: Some(0xB000),
+        crc32: 0x3A5F5721,
+        sha1: "bc2b7c99495fea3eda950ee9e3d6cabe448a452b",
+    },
+    KnownRom {
+        name: "BASIC 4 $C000 (901465-20)",
+        slot: RomSlot::Basic,
+        address: Some(0xC000),
+        crc32: 0x0FC17B9C,
+        sha1: "242f98298931d21eaacb55fe635e44b7fc192b0a",
+    },
+    KnownRom {
+        name: "BASIC 4 $D000 (901465-21)",
+        slot: RomSlot::Basic,
+        address: Some(0xD000),
+        crc32: 0x36D91855,
+        sha1: "1bb236c72c726e8fb029c68f9bfa5ee803faf0a8",
+    },
+    KnownRom {
+        name: "KERNAL 4 (901465-22)",
+        slot: RomSlot::Kernal,
+        address: Some(0xF000),
+        crc32: 0xCC5298A1,
+        sha1: "96a0fa56e0c937da92971d9c99d504e44e898806",
+    },
+    KnownRom {
+        name: "Editor 4, 40 columns, graphics keyboard, 60 Hz (901499-01)",
+        slot: RomSlot::Editor,
+        address: Some(0xE000),
+        crc32: 0x5F85BDF8,
+        sha1: "8cbf086c1ce4dfb2a2fe24c47476dfb878493dee",
+    },
+    KnownRom {
+        name: "Editor 4, 80 columns, business keyboard, 60 Hz (901474-03)",
+        slot: RomSlot::Editor,
+        address: Some(0xE000),
+        crc32: 0x5674DD5E,
+        sha1: "c605fa343fd77c73cbe1e0e9567e2f014f6e7e30",
+    },
+    KnownRom {
+        name: "Editor 4, 80 columns, business keyboard, 50 Hz (901474-04)",
+        slot: RomSlot::Editor,
+        address: Some(0xE000),
+        crc32: 0xABB000E7,
+        sha1: "66887061b6c4ebef7d6efb90af9afd5e2c3b08ba",
+    },
+    KnownRom {
+        name: "Character ROM, original (901447-08)",
+        slot: RomSlot::Characters,
+        address: None,
+        crc32: 0x54F32F45,
+        sha1: "3e067cc621e4beafca2b90cb8f6dba975df2855b",
+    },
+    KnownRom {
+        name: "Character ROM (901447-10)",
+        slot: RomSlot::Characters,
+        address: None,
+        crc32: 0xD8408674,
+        sha1: "0157a2d55b7ac4eaeb38475889ebeea52e2593db",
+    },
+];
+
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+pub fn sha1(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn identify(data: &[u8]) -> Option<&'static KnownRom> {
+    let crc = crc32(data);
+    KNOWN_ROMS
+        .iter()
+        .filter(|rom| rom.crc32 == crc)
+        .find(|rom| rom.sha1 == sha1(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(sha1(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert!(identify(&[0; 0x1000]).is_none());
+    }
+
+    #[test]
+    fn test_bundled_roms_are_known() {
+        let kernal = identify(include_bytes!("../roms/kernal-4.901465-22.bin")).unwrap();
+        assert_eq!(kernal.name, "KERNAL 4 (901465-22)");
+        let characters = identify(include_bytes!("../roms/characters-2.901447-10.bin")).unwrap();
+        assert_eq!(characters.slot, RomSlot::Characters);
+    }
+}
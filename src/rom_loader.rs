@@ -1,8 +1,12 @@
+use crate::config::RomPaths;
+use crate::machine::Model;
+use crate::rom_id::{self, RomSlot};
+use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub struct RomData {
-    pub basic_b000: Vec<u8>,
+    pub basic_b000: Option<Vec<u8>>,
     pub basic_c000: Vec<u8>,
     pub basic_d000: Vec<u8>,
     pub kernal_f000: Vec<u8>,
@@ -10,19 +14,318 @@ pub struct RomData {
     pub char_rom: Vec<u8>,
 }
 
-fn load_rom(name: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let path = Path::new("roms").join(name);
-    let data = fs::read(&path).map_err(|e| format!("Failed to load ROM '{}': {}", name, e))?;
+pub enum OptionSocket {
+    Rom(Vec<u8>),
+    Ram(Vec<u8>),
+}
+
+impl OptionSocket {
+    pub fn rom(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = fs::read(path)
+            .map_err(|e| format!("Failed to load option ROM '{}': {}", path.display(), e))?;
+        if data.is_empty() || data.len() > 0x1000 {
+            return Err(format!(
+                "Option ROM '{}' is {} bytes, expected at most 4096",
+                path.display(),
+                data.len()
+            )
+            .into());
+        }
+        Ok(OptionSocket::Rom(data))
+    }
+
+    pub fn ram() -> Self {
+        OptionSocket::Ram(vec![0; 0x1000])
+    }
+
+    pub fn read(&self, offset: usize) -> u8 {
+        match self {
+            OptionSocket::Rom(data) | OptionSocket::Ram(data) => data[offset % data.len()],
+        }
+    }
+
+    pub fn write(&mut self, offset: usize, val: u8) {
+        if let OptionSocket::Ram(data) = self {
+            data[offset] = val;
+        }
+    }
+}
+
+struct RomSet {
+    basic_b000: &'static [&'static str],
+    basic_c000: &'static [&'static str],
+    basic_d000: &'static [&'static str],
+    kernal_f000: &'static [&'static str],
+    char_rom: &'static [&'static str],
+}
+
+fn rom_set(basic_version: u8) -> RomSet {
+    match basic_version {
+        1 => RomSet {
+            basic_b000: &[],
+            basic_c000: &["basic-1-c000.901439-01.bin", "basic-1-c800.901439-05.bin"],
+            basic_d000: &["basic-1-d000.901439-02.bin", "basic-1-d800.901439-06.bin"],
+            kernal_f000: &["kernal-1-f000.901439-04.bin", "kernal-1-f800.901439-07.bin"],
+            char_rom: &["characters-1.901447-08.bin"],
+        },
+        2 => RomSet {
+            basic_b000: &[],
+            basic_c000: &["basic-2-c000.901465-01.bin"],
+            basic_d000: &["basic-2-d000.901465-02.bin"],
+            kernal_f000: &["kernal-2.901465-03.bin"],
+            char_rom: &["characters-2.901447-10.bin"],
+        },
+        _ => RomSet {
+            basic_b000: &["basic-4-b000.901465-19.bin"],
+            basic_c000: &["basic-4-c000.901465-20.bin"],
+            basic_d000: &["basic-4-d000.901465-21.bin"],
+            kernal_f000: &["kernal-4.901465-22.bin"],
+            char_rom: &["characters-2.901447-10.bin"],
+        },
+    }
+}
+
+pub fn search_path(config_dirs: &[String]) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = config_dirs.iter().map(PathBuf::from).collect();
+    if let Some(paths) = env::var_os("PET_ROM_PATH") {
+        dirs.extend(env::split_paths(&paths));
+    }
+    dirs.push(PathBuf::from("roms"));
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+    if let Some(data_home) = data_home {
+        dirs.push(data_home.join("pet4032/roms"));
+    }
+    if let Some(exe_dir) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        dirs.push(exe_dir.join("roms"));
+    }
+    dirs
+}
+
+#[cfg(feature = "embedded")]
+fn embedded_rom(name: &str) -> Option<&'static [u8]> {
+    match name {
+        "basic-4-b000.901465-19.bin" => Some(include_bytes!("../roms/basic-4-b000.901465-19.bin")),
+        "basic-4-c000.901465-20.bin" => Some(include_bytes!("../roms/basic-4-c000.901465-20.bin")),
+        "basic-4-d000.901465-21.bin" => Some(include_bytes!("../roms/basic-4-d000.901465-21.bin")),
+        "kernal-4.901465-22.bin" => Some(include_bytes!("../roms/kernal-4.901465-22.bin")),
+        "edit-4-40-n-60Hz.901499-01.bin" => {
+            Some(include_bytes!("../roms/edit-4-40-n-60Hz.901499-01.bin"))
+        }
+        "characters-2.901447-10.bin" => Some(include_bytes!("../roms/characters-2.901447-10.bin")),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "embedded"))]
+fn embedded_rom(_name: &str) -> Option<&'static [u8]> {
+    None
+}
+
+fn read_rom(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let data =
+        fs::read(path).map_err(|e| format!("Failed to load ROM '{}': {}", path.display(), e))?;
+    Ok(data)
+}
+
+fn find_rom(name: &str, dirs: &[PathBuf]) -> Result<(String, Vec<u8>), Box<dyn std::error::Error>> {
+    if let Some(path) = dirs
+        .iter()
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+    {
+        let data = read_rom(&path)?;
+        return Ok((path.display().to_string(), data));
+    }
+    if let Some(data) = embedded_rom(name) {
+        return Ok((format!("{} (embedded)", name), data.to_vec()));
+    }
+    let searched: Vec<_> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+    let mut message = format!("ROM '{}' not found in {}", name, searched.join(", "));
+    if cfg!(feature = "embedded") {
+        message.push_str(" (the embedded ROMs only cover the 4032)");
+    }
+    Err(message.into())
+}
+
+fn check_rom(
+    source: &str,
+    data: &[u8],
+    slot: RomSlot,
+    address: Option<u16>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(known) = rom_id::identify(data) else {
+        eprintln!(
+            "{}: unrecognised {} ROM (CRC32 {:08x}, SHA1 {})",
+            source,
+            slot.name(),
+            rom_id::crc32(data),
+            rom_id::sha1(data)
+        );
+        return Ok(());
+    };
+    if known.slot != slot {
+        return Err(format!(
+            "ROM '{}' is the {}, not a {} ROM",
+            source,
+            known.name,
+            slot.name()
+        )
+        .into());
+    }
+    match (known.address, address) {
+        (Some(expected), Some(address)) if expected != address => {
+            return Err(format!(
+                "ROM '{}' is the {}, which belongs at ${:04X}, not ${:04X}",
+                source, known.name, expected, address
+            )
+            .into());
+        }
+        _ => {}
+    }
+    println!("{}: {}", source, known.name);
+    Ok(())
+}
+
+fn load_rom(
+    path: Option<&String>,
+    names: &[&str],
+    slot: RomSlot,
+    address: Option<u16>,
+    dirs: &[PathBuf],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let data = match path {
+        Some(path) => {
+            let data = read_rom(Path::new(path))?;
+            check_rom(path, &data, slot, address)?;
+            data
+        }
+        None => {
+            let mut data = Vec::new();
+            for name in names {
+                let (source, part) = find_rom(name, dirs)?;
+                let part_address = address.map(|address| address + data.len() as u16);
+                check_rom(&source, &part, slot, part_address)?;
+                data.extend(part);
+            }
+            data
+        }
+    };
+    if !slot.sizes().contains(&data.len()) {
+        let source = path.cloned().unwrap_or_else(|| names.join(" + "));
+        return Err(format!(
+            "{} ROM '{}' is {} bytes, expected {}",
+            slot.name(),
+            source,
+            data.len(),
+            slot.sizes()
+                .iter()
+                .map(|size| size.to_string())
+                .collect::<Vec<_>>()
+                .join(" or ")
+        )
+        .into());
+    }
     Ok(data)
 }
 
-pub fn load_roms() -> Result<RomData, Box<dyn std::error::Error>> {
+pub fn load_roms(
+    model: Model,
+    paths: &RomPaths,
+    dirs: &[PathBuf],
+) -> Result<RomData, Box<dyn std::error::Error>> {
+    let set = rom_set(model.basic_version());
+    let basic_b000 = if paths.basic_b000.is_some() || !set.basic_b000.is_empty() {
+        Some(load_rom(
+            paths.basic_b000.as_ref(),
+            set.basic_b000,
+            RomSlot::Basic,
+            Some(0xB000),
+            dirs,
+        )?)
+    } else {
+        None
+    };
     Ok(RomData {
-        basic_b000: load_rom("basic-4-b000.901465-19.bin")?,
-        basic_c000: load_rom("basic-4-c000.901465-20.bin")?,
-        basic_d000: load_rom("basic-4-d000.901465-21.bin")?,
-        kernal_f000: load_rom("kernal-4.901465-22.bin")?,
-        editor_e000: load_rom("edit-4-40-n-60Hz.901499-01.bin")?,
-        char_rom: load_rom("characters-2.901447-10.bin")?,
+        basic_b000,
+        basic_c000: load_rom(
+            paths.basic_c000.as_ref(),
+            set.basic_c000,
+            RomSlot::Basic,
+            Some(0xC000),
+            dirs,
+        )?,
+        basic_d000: load_rom(
+            paths.basic_d000.as_ref(),
+            set.basic_d000,
+            RomSlot::Basic,
+            Some(0xD000),
+            dirs,
+        )?,
+        kernal_f000: load_rom(
+            paths.kernal.as_ref(),
+            set.kernal_f000,
+            RomSlot::Kernal,
+            Some(0xF000),
+            dirs,
+        )?,
+        editor_e000: load_rom(
+            paths.editor.as_ref(),
+            &[model.editor_rom()],
+            RomSlot::Editor,
+            Some(0xE000),
+            dirs,
+        )?,
+        char_rom: load_rom(
+            paths.characters.as_ref(),
+            set.char_rom,
+            RomSlot::Characters,
+            None,
+            dirs,
+        )?,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_option_sockets() {
+        let mut rom = OptionSocket::Rom((0..0x800).map(|i| i as u8).collect());
+        rom.write(0x10, 0xAA);
+        assert_eq!(rom.read(0x10), 0x10);
+        assert_eq!(rom.read(0x810), 0x10);
+
+        let mut ram = OptionSocket::ram();
+        ram.write(0xFFF, 0x55);
+        assert_eq!(ram.read(0xFFF), 0x55);
+    }
+
+    #[test]
+    fn test_check_rom_address() {
+        let basic_d000 = include_bytes!("../roms/basic-4-d000.901465-21.bin");
+        assert!(check_rom("d000", basic_d000, RomSlot::Basic, Some(0xD000)).is_ok());
+        let error = check_rom("d000", basic_d000, RomSlot::Basic, Some(0xC000)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "ROM 'd000' is the BASIC 4 $D000 (901465-21), which belongs at $D000, not $C000"
+        );
+        assert!(check_rom("d000", basic_d000, RomSlot::Kernal, Some(0xF000)).is_err());
+
+        let characters = include_bytes!("../roms/characters-2.901447-10.bin");
+        assert!(check_rom("chars", characters, RomSlot::Characters, None).is_ok());
+    }
+
+    #[test]
+    fn test_missing_rom_error() {
+        let error = find_rom("basic-2-c000.901465-01.bin", &[]).unwrap_err();
+        let message = error.to_string();
+        assert!(message.starts_with("ROM 'basic-2-c000.901465-01.bin' not found"));
+        assert_eq!(message.contains("embedded"), cfg!(feature = "embedded"));
+    }
+}
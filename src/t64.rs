@@ -0,0 +1,123 @@
+use crate::cbm_dos::petscii_name;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+const HEADER_SIZE: usize = 0x40;
+const ENTRY_SIZE: usize = 0x20;
+
+pub struct T64Entry {
+    pub name: String,
+    pub load_address: u16,
+    offset: usize,
+    length: usize,
+}
+
+pub struct T64Archive {
+    pub entries: Vec<T64Entry>,
+    data: Vec<u8>,
+}
+
+impl T64Archive {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let data = fs::read(path)?;
+        Self::parse(data).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn parse(data: Vec<u8>) -> Result<Self, Box<dyn Error>> {
+        if data.len() < HEADER_SIZE || !data.starts_with(b"C64") {
+            return Err("Not a T64 archive".into());
+        }
+        let max_entries = u16::from_le_bytes([data[0x22], data[0x23]]) as usize;
+
+        let mut entries = Vec::new();
+        for index in 0..max_entries.max(1) {
+            let start = HEADER_SIZE + index * ENTRY_SIZE;
+            let Some(raw) = data.get(start..start + ENTRY_SIZE) else {
+                break;
+            };
+            if raw[0] == 0 {
+                continue;
+            }
+            let load_address = u16::from_le_bytes([raw[2], raw[3]]);
+            let end_address = u16::from_le_bytes([raw[4], raw[5]]);
+            let offset = u32::from_le_bytes([raw[8], raw[9], raw[10], raw[11]]) as usize;
+            if offset >= data.len() {
+                continue;
+            }
+            entries.push(T64Entry {
+                name: petscii_name(&raw[0x10..0x20]),
+                load_address,
+                offset,
+                length: end_address.wrapping_sub(load_address) as usize,
+            });
+        }
+
+        // Many archives carry a bogus end address, so never read past the
+        // next entry's data or the end of the file.
+        let mut offsets: Vec<usize> = entries.iter().map(|e| e.offset).collect();
+        offsets.push(data.len());
+        offsets.sort();
+        for entry in &mut entries {
+            let limit = offsets
+                .iter()
+                .copied()
+                .find(|&o| o > entry.offset)
+                .unwrap_or(data.len());
+            let available = limit - entry.offset;
+            if entry.length == 0 || entry.length > available {
+                entry.length = available;
+            }
+        }
+
+        Ok(Self { entries, data })
+    }
+
+    pub fn load(&self, index: usize) -> Result<(u16, Vec<u8>), Box<dyn Error>> {
+        let entry = self
+            .entries
+            .get(index)
+            .ok_or_else(|| format!("No entry {} in T64 archive", index))?;
+        let data = self.data[entry.offset..entry.offset + entry.length].to_vec();
+        Ok((entry.load_address, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(entries: &[(&str, u16, u16, &[u8])]) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_SIZE + entries.len() * ENTRY_SIZE];
+        data[..14].copy_from_slice(b"C64S tape file");
+        data[0x22] = entries.len() as u8;
+        data[0x24] = entries.len() as u8;
+        for (i, (name, start, end, payload)) in entries.iter().enumerate() {
+            let offset = data.len() as u32;
+            let raw = &mut data[HEADER_SIZE + i * ENTRY_SIZE..][..ENTRY_SIZE];
+            raw[0] = 1;
+            raw[1] = 0x82;
+            raw[2..4].copy_from_slice(&start.to_le_bytes());
+            raw[4..6].copy_from_slice(&end.to_le_bytes());
+            raw[8..12].copy_from_slice(&offset.to_le_bytes());
+            raw[0x10..0x20].fill(0x20);
+            raw[0x10..0x10 + name.len()].copy_from_slice(name.as_bytes());
+            data.extend_from_slice(payload);
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let data = archive(&[
+            ("HELLO", 0x0401, 0x0404, &[1, 2, 3]),
+            ("GAME", 0x1000, 0xC3C6, &[4, 5]),
+        ]);
+        let t64 = T64Archive::parse(data).unwrap();
+        assert_eq!(t64.entries.len(), 2);
+        assert_eq!(t64.entries[0].name, "HELLO");
+        assert_eq!(t64.load(0).unwrap(), (0x0401, vec![1, 2, 3]));
+        assert_eq!(t64.load(1).unwrap(), (0x1000, vec![4, 5]));
+        assert!(t64.load(2).is_err());
+    }
+}
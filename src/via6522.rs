@@ -1,3 +1,22 @@
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ViaControlLines {
+    pub ca1: bool,
+    pub ca2: bool,
+    pub cb1: bool,
+    pub cb2: bool,
+}
+
+impl ViaControlLines {
+    pub fn idle() -> Self {
+        Self {
+            ca1: true,
+            ca2: true,
+            cb1: true,
+            cb2: true,
+        }
+    }
+}
+
 pub struct Via6522 {
     ora: u8,
     ira: u8,
@@ -8,11 +27,21 @@ pub struct Via6522 {
     t1_counter: u16,
     t1_latch: u16,
     t1_running: bool,
+    t1_reload: bool,
+    pb7: bool,
     t2_counter: u16,
     t2_latch_low: u8,
+    t2_running: bool,
     sr: u8,
+    sr_timer: u16,
+    sr_clock: bool,
+    sr_bits: u8,
+    sr_out: bool,
     acr: u8,
     pcr: u8,
+    inputs: ViaControlLines,
+    ca2_out: bool,
+    cb2_out: bool,
     ifr: u8,
     ier: u8,
     pub irq_out: bool,
@@ -30,11 +59,21 @@ impl Via6522 {
             t1_counter: 0xFFFF,
             t1_latch: 0,
             t1_running: false,
+            t1_reload: false,
+            pb7: true,
             t2_counter: 0xFFFF,
             t2_latch_low: 0,
+            t2_running: false,
             sr: 0,
+            sr_timer: 0,
+            sr_clock: true,
+            sr_bits: 8,
+            sr_out: true,
             acr: 0,
             pcr: 0,
+            inputs: ViaControlLines::idle(),
+            ca2_out: true,
+            cb2_out: true,
             ifr: 0,
             ier: 0,
             irq_out: false,
@@ -43,8 +82,14 @@ impl Via6522 {
 
     pub fn write_register(&mut self, reg: u8, val: u8) {
         match reg {
-            0x00 => self.orb = val,
-            0x01 => self.ora = val,
+            0x00 => {
+                self.orb = val;
+                self.port_b_access(true);
+            }
+            0x01 => {
+                self.ora = val;
+                self.port_a_access();
+            }
             0x02 => self.ddrb = val,
             0x03 => self.ddra = val,
             0x04 => self.t1_latch = (self.t1_latch & 0xFF00) | (val as u16),
@@ -52,6 +97,8 @@ impl Via6522 {
                 self.t1_latch = (self.t1_latch & 0x00FF) | ((val as u16) << 8);
                 self.t1_counter = self.t1_latch;
                 self.t1_running = true;
+                self.t1_reload = false;
+                self.pb7 = false;
                 self.ifr &= !0x40;
                 self.update_irq();
             }
@@ -64,10 +111,14 @@ impl Via6522 {
             0x08 => self.t2_latch_low = val,
             0x09 => {
                 self.t2_counter = ((val as u16) << 8) | (self.t2_latch_low as u16);
+                self.t2_running = true;
                 self.ifr &= !0x20;
                 self.update_irq();
             }
-            0x0A => self.sr = val,
+            0x0A => {
+                self.sr = val;
+                self.start_shift();
+            }
             0x0B => self.acr = val,
             0x0C => self.pcr = val,
             0x0D => {
@@ -89,8 +140,14 @@ impl Via6522 {
 
     pub fn read_register(&mut self, reg: u8) -> u8 {
         match reg {
-            0x00 => (self.irb & !self.ddrb) | (self.orb & self.ddrb),
-            0x01 => (self.ira & !self.ddra) | (self.ora & self.ddra),
+            0x00 => {
+                self.port_b_access(false);
+                self.with_pb7((self.irb & !self.ddrb) | (self.orb & self.ddrb))
+            }
+            0x01 => {
+                self.port_a_access();
+                (self.ira & !self.ddra) | (self.ora & self.ddra)
+            }
             0x02 => self.ddrb,
             0x03 => self.ddra,
             0x04 => {
@@ -107,7 +164,10 @@ impl Via6522 {
                 (self.t2_counter & 0xFF) as u8
             }
             0x09 => (self.t2_counter >> 8) as u8,
-            0x0A => self.sr,
+            0x0A => {
+                self.start_shift();
+                self.sr
+            }
             0x0B => self.acr,
             0x0C => self.pcr,
             0x0D => {
@@ -125,20 +185,206 @@ impl Via6522 {
     }
 
     pub fn tick(&mut self, cycles: u32) {
-        if self.t1_running {
-            if self.t1_counter <= cycles as u16 {
-                self.ifr |= 0x40;
+        if (self.pcr >> 1) & 0x07 == 0b101 {
+            self.ca2_out = true;
+        }
+        if self.pcr >> 5 == 0b101 {
+            self.cb2_out = true;
+        }
+        for _ in 0..cycles {
+            self.tick_t1();
+            if (self.acr & 0x20) == 0 {
+                self.count_t2();
+            }
+            self.tick_shift_register();
+        }
+    }
 
-                if (self.acr & 0x40) != 0 {
-                    self.t1_counter = self.t1_latch.wrapping_sub(cycles as u16 - self.t1_counter);
-                } else {
-                    self.t1_running = false;
-                    self.t1_counter = self.t1_counter.wrapping_sub(cycles as u16);
+    fn tick_t1(&mut self) {
+        if self.t1_reload {
+            self.t1_reload = false;
+            self.t1_counter = self.t1_latch;
+            return;
+        }
+        let (counter, underflow) = self.t1_counter.overflowing_sub(1);
+        self.t1_counter = counter;
+        if !underflow || !self.t1_running {
+            return;
+        }
+        self.ifr |= 0x40;
+        if (self.acr & 0x40) != 0 {
+            self.t1_reload = true;
+            self.pb7 = !self.pb7;
+        } else {
+            self.t1_running = false;
+            self.pb7 = true;
+        }
+        self.update_irq();
+    }
+
+    fn count_t2(&mut self) {
+        let (counter, underflow) = self.t2_counter.overflowing_sub(1);
+        self.t2_counter = counter;
+        let timed_out = if (self.acr & 0x20) != 0 {
+            counter == 0
+        } else {
+            underflow
+        };
+        if timed_out && self.t2_running {
+            self.t2_running = false;
+            self.ifr |= 0x20;
+            self.update_irq();
+        }
+    }
+
+    fn start_shift(&mut self) {
+        self.sr_bits = 0;
+        self.sr_timer = self.t2_latch_low as u16 + 2;
+        self.ifr &= !0x04;
+        self.update_irq();
+    }
+
+    fn tick_shift_register(&mut self) {
+        let mode = (self.acr >> 2) & 0x07;
+        let free_running = mode == 0b100;
+        if mode == 0 || (self.sr_bits >= 8 && !free_running) {
+            return;
+        }
+        match mode & 0x03 {
+            0b00 | 0b01 => {
+                self.sr_timer = self.sr_timer.saturating_sub(1);
+                if self.sr_timer != 0 {
+                    return;
                 }
-                self.update_irq();
-            } else {
-                self.t1_counter -= cycles as u16;
+                self.sr_timer = self.t2_latch_low as u16 + 2;
             }
+            0b10 => {}
+            _ => return,
+        }
+        self.sr_clock = !self.sr_clock;
+        if self.sr_clock {
+            self.shift_bit(mode & 0x04 != 0, free_running);
+        }
+    }
+
+    fn shift_bit(&mut self, output: bool, free_running: bool) {
+        if output {
+            self.sr_out = self.sr & 0x80 != 0;
+            self.sr = self.sr.rotate_left(1);
+        } else {
+            self.sr = (self.sr << 1) | self.inputs.cb2 as u8;
+        }
+        if free_running {
+            return;
+        }
+        self.sr_bits += 1;
+        if self.sr_bits == 8 {
+            self.ifr |= 0x04;
+            self.update_irq();
+        }
+    }
+
+    fn port_a_access(&mut self) {
+        let control = (self.pcr >> 1) & 0x07;
+        self.ifr &= !0x02;
+        if !Self::independent_interrupt(control) {
+            self.ifr &= !0x01;
+        }
+        if control == 0b100 || control == 0b101 {
+            self.ca2_out = false;
+        }
+        self.update_irq();
+    }
+
+    fn port_b_access(&mut self, write: bool) {
+        let control = self.pcr >> 5;
+        self.ifr &= !0x10;
+        if !Self::independent_interrupt(control) {
+            self.ifr &= !0x08;
+        }
+        if write && (control == 0b100 || control == 0b101) {
+            self.cb2_out = false;
+        }
+        self.update_irq();
+    }
+
+    fn independent_interrupt(control: u8) -> bool {
+        (control & 0b101) == 0b001
+    }
+
+    fn active_edge(old: bool, new: bool, positive: bool) -> bool {
+        old != new && new == positive
+    }
+
+    fn c2_output(control: u8, handshake: bool) -> Option<bool> {
+        match control {
+            0b100 | 0b101 => Some(handshake),
+            0b110 => Some(false),
+            0b111 => Some(true),
+            _ => None,
+        }
+    }
+
+    pub fn control_lines(&self) -> ViaControlLines {
+        let cb2 = if (self.acr & 0x10) != 0 {
+            self.sr_out
+        } else {
+            Self::c2_output(self.pcr >> 5, self.cb2_out).unwrap_or(self.inputs.cb2)
+        };
+        ViaControlLines {
+            ca2: Self::c2_output((self.pcr >> 1) & 0x07, self.ca2_out).unwrap_or(self.inputs.ca2),
+            cb2,
+            ..self.inputs
+        }
+    }
+
+    pub fn set_control_lines(&mut self, lines: ViaControlLines) {
+        let old = self.inputs;
+        self.inputs = lines;
+
+        if Self::active_edge(old.ca1, lines.ca1, (self.pcr & 0x01) != 0) {
+            self.ifr |= 0x02;
+            if (self.pcr >> 1) & 0x07 == 0b100 {
+                self.ca2_out = true;
+            }
+        }
+        if (self.pcr & 0x08) == 0 && Self::active_edge(old.ca2, lines.ca2, (self.pcr & 0x04) != 0) {
+            self.ifr |= 0x01;
+        }
+        if Self::active_edge(old.cb1, lines.cb1, (self.pcr & 0x10) != 0) {
+            self.ifr |= 0x10;
+            if self.pcr >> 5 == 0b100 {
+                self.cb2_out = true;
+            }
+        }
+        if (self.pcr & 0x80) == 0 && Self::active_edge(old.cb2, lines.cb2, (self.pcr & 0x40) != 0) {
+            self.ifr |= 0x08;
+        }
+
+        let mode = (self.acr >> 2) & 0x07;
+        if (mode & 0x03) == 0x03 && lines.cb1 && !old.cb1 && self.sr_bits < 8 {
+            self.shift_bit((mode & 0x04) != 0, false);
+        }
+        self.update_irq();
+    }
+
+    pub fn set_port_b_input(&mut self, val: u8) {
+        let pb6_falling = (self.irb & 0x40) != 0 && (val & 0x40) == 0;
+        self.irb = val;
+        if pb6_falling && (self.acr & 0x20) != 0 {
+            self.count_t2();
+        }
+    }
+
+    pub fn port_b_output(&self) -> u8 {
+        self.with_pb7((self.orb & self.ddrb) | !self.ddrb)
+    }
+
+    fn with_pb7(&self, value: u8) -> u8 {
+        match ((self.acr & 0x80) != 0, self.pb7) {
+            (false, _) => value,
+            (true, true) => value | 0x80,
+            (true, false) => value & 0x7F,
         }
     }
 
@@ -153,3 +399,125 @@ impl Via6522 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_free_running_shift_out() {
+        let mut via = Via6522::new();
+        via.write_register(0x0B, 0x10);
+        via.write_register(0x08, 8);
+        via.write_register(0x0A, 0x0F);
+        let mut edges = 0;
+        let mut level = via.control_lines().cb2;
+        for _ in 0..1600 {
+            via.tick(1);
+            if via.control_lines().cb2 != level {
+                level = via.control_lines().cb2;
+                edges += 1;
+            }
+        }
+        assert_eq!(edges, 20);
+        assert!(!via.irq_out);
+    }
+
+    #[test]
+    fn test_t1_continuous_period_and_pb7() {
+        let mut via = Via6522::new();
+        via.write_register(0x0B, 0xC0);
+        via.write_register(0x0E, 0xC0);
+        via.write_register(0x04, 10);
+        via.write_register(0x05, 0);
+        assert_eq!(via.port_b_output() & 0x80, 0);
+        let mut interrupts = Vec::new();
+        for cycle in 1..=40 {
+            via.tick(1);
+            if via.irq_out {
+                interrupts.push(cycle);
+                via.read_register(0x04);
+            }
+        }
+        assert_eq!(interrupts, vec![11, 23, 35]);
+        assert_eq!(via.port_b_output() & 0x80, 0x80);
+    }
+
+    #[test]
+    fn test_t2_one_shot_and_pulse_counting() {
+        let mut via = Via6522::new();
+        via.write_register(0x08, 5);
+        via.write_register(0x09, 0);
+        via.tick(5);
+        assert_eq!(via.read_register(0x0D) & 0x20, 0);
+        via.tick(1);
+        assert_eq!(via.read_register(0x0D) & 0x20, 0x20);
+        via.tick(0x10000);
+        via.read_register(0x08);
+        via.tick(0x10000);
+        assert_eq!(via.read_register(0x0D) & 0x20, 0);
+
+        via.write_register(0x0B, 0x20);
+        via.write_register(0x08, 3);
+        via.write_register(0x09, 0);
+        via.tick(100);
+        for _ in 0..3 {
+            via.set_port_b_input(0xFF);
+            via.set_port_b_input(0xBF);
+        }
+        assert_eq!(via.read_register(0x0D) & 0x20, 0x20);
+    }
+
+    #[test]
+    fn test_t2_one_shot_rearm() {
+        let mut via = Via6522::new();
+        via.write_register(0x0E, 0xA0);
+        via.write_register(0x08, 2);
+        via.write_register(0x09, 0);
+        via.tick(3);
+        assert!(via.irq_out);
+        assert_eq!(via.read_register(0x09), 0xFF);
+
+        via.write_register(0x09, 0);
+        assert!(!via.irq_out);
+        via.tick(2);
+        assert!(!via.irq_out);
+        via.tick(1);
+        assert!(via.irq_out);
+        via.read_register(0x08);
+        assert!(!via.irq_out);
+    }
+
+    #[test]
+    fn test_control_line_edges_and_handshake() {
+        let mut via = Via6522::new();
+        via.write_register(0x0C, 0x09);
+        let mut lines = ViaControlLines::idle();
+        lines.ca1 = false;
+        via.set_control_lines(lines);
+        assert_eq!(via.read_register(0x0D) & 0x02, 0);
+        lines.ca1 = true;
+        via.set_control_lines(lines);
+        assert_eq!(via.read_register(0x0D) & 0x02, 0x02);
+        via.read_register(0x01);
+        assert_eq!(via.read_register(0x0D) & 0x02, 0);
+        assert!(!via.control_lines().ca2);
+        lines.ca1 = false;
+        via.set_control_lines(lines);
+        lines.ca1 = true;
+        via.set_control_lines(lines);
+        assert!(via.control_lines().ca2);
+
+        via.write_register(0x0C, 0xA0);
+        via.write_register(0x00, 0x55);
+        assert!(!via.control_lines().cb2);
+        via.tick(1);
+        assert!(via.control_lines().cb2);
+
+        via.write_register(0x0C, 0x20);
+        lines.cb2 = false;
+        via.set_control_lines(lines);
+        via.read_register(0x00);
+        assert_eq!(via.read_register(0x0D) & 0x08, 0x08);
+    }
+}